@@ -0,0 +1,65 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+// Inputs
+// - back and belly color, blended across the body
+// - stripes running across the body along u
+// - spots scattered on a jittered grid
+// - a per-creature seed
+// - per-vertebra tints from the vertex colors
+
+@group(1) @binding(0) var<uniform> back_color: vec4<f32>;
+@group(1) @binding(1) var<uniform> belly_color: vec4<f32>;
+@group(1) @binding(2) var<uniform> stripe_color: vec4<f32>;
+@group(1) @binding(3) var<uniform> spot_color: vec4<f32>;
+@group(1) @binding(4) var<uniform> stripe_frequency: f32;
+@group(1) @binding(5) var<uniform> stripe_width: f32;
+@group(1) @binding(6) var<uniform> spot_frequency: f32;
+@group(1) @binding(7) var<uniform> spot_size: f32;
+@group(1) @binding(8) var<uniform> spot_chance: f32;
+@group(1) @binding(9) var<uniform> seed: f32;
+
+// Spots across the width of the body
+const SPOT_COLUMNS: f32 = 3.0;
+
+fn hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let u = mesh.uv.x;
+    let v = mesh.uv.y;
+
+    // 0 along the spine, 1 at either edge of the body
+    let flank = abs(v - 0.5) * 2.0;
+    var color = mix(back_color, belly_color, smoothstep(0.2, 1.0, flank));
+
+    if (stripe_frequency > 0.0) {
+        // Stripes bow backwards towards the flanks, each individual with its own offset and bend
+        let bend = 0.1 + 0.2 * hash(vec2<f32>(seed, 1.0));
+        let stripe = fract(u * stripe_frequency + seed + flank * flank * bend);
+
+        if (stripe < stripe_width) {
+            color = mix(color, stripe_color, stripe_color.a);
+        }
+    }
+
+    if (spot_frequency > 0.0) {
+        let grid = vec2<f32>(u * spot_frequency, v * SPOT_COLUMNS);
+        let cell = floor(grid) + vec2<f32>(seed * 7.0, seed * 13.0);
+        let local = fract(grid);
+
+        let center = vec2<f32>(hash(cell), hash(cell + vec2<f32>(17.0, 31.0))) * 0.5 + 0.25;
+        let present = hash(cell + vec2<f32>(53.0, 89.0)) < spot_chance;
+
+        if (present && distance(local, center) < spot_size) {
+            color = mix(color, spot_color, spot_color.a);
+        }
+    }
+
+#ifdef VERTEX_COLORS
+    color = color * mesh.color;
+#endif
+
+    return vec4<f32>(color.rgb, 1.0);
+}
@@ -1,15 +1,14 @@
 #import bevy_sprite::mesh2d_vertex_output::VertexOutput
-#import bevy_pbr::{
-    mesh_view_bindings::view,
-    utils::coords_to_viewport_uv,
-}
 
 // we can import items from shader modules in the assets folder with a quoted path
 
 // Inputs
 // - background color
 // - shadow color
-// - shadow offset distance
+// - shadow offset in pixels and blur radius in pixels, 0 for hard shadows
+// - ground texture, shown wherever nothing stands
+// - height texture, shadows cast from higher up land farther away
+// - outline color and thickness in texture pixels, 0 for no outline
 
 
 
@@ -18,19 +17,112 @@
 @group(1) @binding(2) var screen_texture: texture_2d<f32>;
 @group(1) @binding(3) var screen_texture_sampler: sampler;
 @group(1) @binding(4) var<uniform> shadow_offset: vec2<f32>;
+@group(1) @binding(5) var ground_texture: texture_2d<f32>;
+@group(1) @binding(6) var ground_texture_sampler: sampler;
+@group(1) @binding(7) var<uniform> outline_color: vec4<f32>;
+@group(1) @binding(8) var<uniform> outline_thickness: u32;
+@group(1) @binding(9) var<uniform> shadow_blur: u32;
+@group(1) @binding(10) var height_texture: texture_2d<f32>;
+@group(1) @binding(11) var height_texture_sampler: sampler;
+@group(1) @binding(12) var<uniform> shadow_height_scale: f32;
+
+// Must match `MAX_ELEVATION` in `render_shadows.rs`
+const MAX_ELEVATION: f32 = 64.0;
+// Farthest a shadow is searched for past its normal offset, in pixels
+const MAX_HEIGHT_OFFSET: i32 = 32;
+
+fn is_background(color: vec4<f32>) -> bool {
+    return length(color - background_color) < 0.01;
+}
+
+// Whether anything but background lies within `outline_thickness` pixels of `pixel`
+fn near_edge(pixel: vec2<i32>) -> bool {
+    let thickness = i32(outline_thickness);
+    let last_pixel = vec2<i32>(textureDimensions(screen_texture)) - 1;
+
+    for (var y = -thickness; y <= thickness; y++) {
+        for (var x = -thickness; x <= thickness; x++) {
+            if (x * x + y * y > thickness * thickness) {
+                continue;
+            }
+
+            let neighbour = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), last_pixel);
+            if (!is_background(textureLoad(screen_texture, neighbour, 0))) {
+                return true;
+            }
+        }
+    }
+    return false;
+}
+
+// Whether something at `pixel` casts a shadow `extra` pixels past the normal offset,
+// which only parts that high up do
+fn casts_from(pixel: vec2<i32>, extra: i32) -> bool {
+    if (is_background(textureLoad(screen_texture, pixel, 0))) {
+        return false;
+    }
+
+    let elevation = textureLoad(height_texture, pixel, 0).r * MAX_ELEVATION;
+    return abs(elevation * shadow_height_scale - f32(extra)) < 0.5;
+}
+
+// Whether anything casts a shadow onto `pixel`. Walks back along the shadow's direction
+// looking for a part whose height puts its shadow exactly here.
+fn in_shadow(pixel: vec2<i32>) -> bool {
+    let last_pixel = vec2<i32>(textureDimensions(screen_texture)) - 1;
+    let direction = normalize(shadow_offset + vec2<f32>(1e-5, 0.0));
+    let reach = min(i32(ceil(MAX_ELEVATION * shadow_height_scale)), MAX_HEIGHT_OFFSET);
+
+    for (var extra = 0; extra <= reach; extra++) {
+        let offset = shadow_offset + direction * f32(extra);
+        let origin = clamp(pixel - vec2<i32>(round(offset)), vec2<i32>(0), last_pixel);
+
+        if (casts_from(origin, extra)) {
+            return true;
+        }
+    }
+    return false;
+}
+
+// How much of the shadow reaches `pixel`, from 0 to 1. Without blur this is whether the
+// pixel itself is in shadow, otherwise the share of pixels around it that are.
+fn shadow_coverage(pixel: vec2<i32>) -> f32 {
+    let blur = i32(shadow_blur);
+
+    var covered = 0.0;
+    var taps = 0.0;
+
+    for (var y = -blur; y <= blur; y++) {
+        for (var x = -blur; x <= blur; x++) {
+            if (x * x + y * y > blur * blur) {
+                continue;
+            }
+
+            if (in_shadow(pixel + vec2<i32>(x, y))) {
+                covered += 1.0;
+            }
+            taps += 1.0;
+        }
+    }
+    return covered / taps;
+}
 
 @fragment
 fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
-    let viewport_uv = coords_to_viewport_uv(mesh.position.xy, view.viewport);
-    let current_color = textureSample(screen_texture, screen_texture_sampler, viewport_uv);
-
-    let offset_color = textureSample(screen_texture, screen_texture_sampler, viewport_uv + shadow_offset);
+    // The quad covers the render texture exactly, letterboxed or not
+    let uv = mesh.uv;
+    let current_color = textureSample(screen_texture, screen_texture_sampler, uv);
+    let ground_color = textureSample(ground_texture, ground_texture_sampler, uv);
 
-    if (length(current_color - background_color) < 0.01) {
+    if (is_background(current_color)) {
+        let dimensions = vec2<i32>(textureDimensions(screen_texture));
+        let pixel = min(vec2<i32>(uv * vec2<f32>(dimensions)), dimensions - 1);
 
-        if (length(offset_color - background_color) > 0.01) {
-            return shadow_color;
+        if (outline_thickness > 0u && near_edge(pixel)) {
+            return outline_color;
         }
+
+        return mix(ground_color, shadow_color, shadow_coverage(pixel));
     }
     return current_color;
 }
\ No newline at end of file
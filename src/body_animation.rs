@@ -0,0 +1,128 @@
+// Animates how wide each vertebra is drawn, on top of the width it was spawned with.
+// The body mesh picks the changes up through `Vertebra::width_scale`.
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::top_down_crawler::*;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BreathingDefinition {
+    // Breaths per second
+    pub rate: f32,
+    // Extra width at the deepest breath, relative to each vertebra's own width
+    pub amount: f32,
+    // Vertebrae that swell, from the neck back. Strongest in the middle of the range.
+    pub from: usize,
+    pub to: usize,
+}
+
+// Slowly swells part of the body in and out. Lives on a creature's root next to `Creature`.
+#[derive(Component)]
+pub struct Breathing {
+    pub rate: f32,
+    pub amount: f32,
+    pub from: usize,
+    pub to: usize,
+}
+
+impl From<&BreathingDefinition> for Breathing {
+    fn from(definition: &BreathingDefinition) -> Self {
+        Self {
+            rate: definition.rate,
+            amount: definition.amount,
+            from: definition.from,
+            to: definition.to,
+        }
+    }
+}
+
+// Something swallowed, bulging the body as it travels from the neck to the tail
+#[derive(Debug, Clone, Copy)]
+pub struct Lump {
+    // Vertebrae from the neck to the middle of the lump
+    pub position: f32,
+    // Vertebrae travelled per second
+    pub speed: f32,
+    // Extra width in the middle of the lump, relative to each vertebra's own width
+    pub size: f32,
+    // Vertebrae covered by the lump
+    pub length: f32,
+}
+
+// Everything a creature is currently swallowing. Lives on its root next to `Creature`.
+#[derive(Component, Default)]
+pub struct Lumps(pub Vec<Lump>);
+
+impl Lumps {
+    pub fn swallow(&mut self, size: f32, length: f32, speed: f32) {
+        // Starts just in front of the neck so it swells in instead of popping up
+        self.0.push(Lump {
+            position: -length * 0.5,
+            speed,
+            size,
+            length,
+        });
+    }
+}
+
+pub fn animate_body_width(
+    mut creature_q: Query<(&Spine, Option<&Breathing>, Option<&mut Lumps>)>,
+    mut vertebra_q: Query<&mut Vertebra>,
+    time: Res<Time>,
+) {
+    for (spine, breathing, lumps) in creature_q.iter_mut() {
+        if breathing.is_none() && lumps.is_none() { continue; }
+
+        let body_len = spine.vertebrae.len() as f32;
+
+        let lumps = lumps.map(|mut lumps| {
+            for lump in lumps.0.iter_mut() {
+                lump.position += lump.speed * time.delta_seconds();
+            }
+
+            // Gone once the whole lump has passed the tip of the tail
+            lumps.0.retain(|lump| lump.position - lump.length * 0.5 < body_len);
+            lumps
+        });
+
+        let breath = breathing.map(|breathing| {
+            let phase = (time.elapsed_seconds() * breathing.rate * PI * 2.0).sin() * 0.5 + 0.5;
+            (breathing, phase)
+        });
+
+        let mut vertebrae = vertebra_q.iter_many_mut(&spine.vertebrae);
+        let mut index = 0;
+
+        while let Some(mut vertebra) = vertebrae.fetch_next() {
+            let i = index as f32;
+            let mut scale = 1.0;
+
+            if let Some((breathing, phase)) = breath {
+                if (breathing.from..=breathing.to).contains(&index) {
+                    let span = (breathing.to - breathing.from) as f32 + 2.0;
+                    let window = (PI * (i - breathing.from as f32 + 1.0) / span).sin();
+                    scale += breathing.amount * phase * window;
+                }
+            }
+
+            if let Some(lumps) = &lumps {
+                for lump in lumps.0.iter() {
+                    let offset = (i - lump.position) / (lump.length * 0.5).max(f32::EPSILON);
+                    if offset.abs() < 1.0 {
+                        scale += lump.size * (offset * PI * 0.5).cos().powi(2);
+                    }
+                }
+            }
+
+            // Only touch vertebrae whose width actually changed, so idle bodies are not rebuilt
+            if vertebra.width_scale != scale {
+                vertebra.width_scale = scale;
+            }
+
+            index += 1;
+        }
+    }
+}
@@ -0,0 +1,97 @@
+// Procedural markings drawn straight from the body mesh UVs, so every individual of a
+// species can look a little different without any authored art.
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef},
+    sprite::Material2d,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PatternDefinition {
+    // Along the middle of the back
+    pub back_color: Color,
+    // Towards the flanks
+    pub belly_color: Color,
+
+    pub stripe_color: Color,
+    // Stripes per unit of u, none if 0
+    #[serde(default)]
+    pub stripe_frequency: f32,
+    // Share of every stripe period that is stripe, from 0 to 1
+    #[serde(default)]
+    pub stripe_width: f32,
+
+    pub spot_color: Color,
+    // Rows of spots per unit of u, none if 0
+    #[serde(default)]
+    pub spot_frequency: f32,
+    // Spot radius relative to the cell it sits in, from 0 to 0.5
+    #[serde(default)]
+    pub spot_size: f32,
+    // Chance of a cell holding a spot at all
+    #[serde(default = "default_spot_chance")]
+    pub spot_chance: f32,
+}
+
+fn default_spot_chance() -> f32 {
+    0.5
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct BodyPatternMaterial {
+    #[uniform(0)]
+    pub back_color: Color,
+    #[uniform(1)]
+    pub belly_color: Color,
+    #[uniform(2)]
+    pub stripe_color: Color,
+    #[uniform(3)]
+    pub spot_color: Color,
+
+    #[uniform(4)]
+    pub stripe_frequency: f32,
+    #[uniform(5)]
+    pub stripe_width: f32,
+    #[uniform(6)]
+    pub spot_frequency: f32,
+    #[uniform(7)]
+    pub spot_size: f32,
+    #[uniform(8)]
+    pub spot_chance: f32,
+
+    // Shifts the stripes and shuffles the spots, so no two individuals match
+    #[uniform(9)]
+    pub seed: f32,
+}
+
+impl BodyPatternMaterial {
+    pub fn new(definition: &PatternDefinition, seed: f32) -> Self {
+        Self {
+            back_color: definition.back_color,
+            belly_color: definition.belly_color,
+            stripe_color: definition.stripe_color,
+            spot_color: definition.spot_color,
+
+            stripe_frequency: definition.stripe_frequency,
+            stripe_width: definition.stripe_width.clamp(0.0, 1.0),
+            spot_frequency: definition.spot_frequency,
+            spot_size: definition.spot_size.clamp(0.0, 0.5),
+            spot_chance: definition.spot_chance.clamp(0.0, 1.0),
+
+            seed,
+        }
+    }
+}
+
+impl Material2d for BodyPatternMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/body_pattern.wgsl".into()
+    }
+}
+
+// Draws the body with a `BodyPatternMaterial` instead of a flat color or a skin.
+// Lives on the root next to `Creature`, so the seed survives the body mesh being rebuilt.
+#[derive(Component)]
+pub struct BodyPatternSkin(pub Handle<BodyPatternMaterial>);
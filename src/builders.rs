@@ -1,29 +1,55 @@
 use std::cell::RefMut;
 
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle, math::*, render::mesh::shape::Circle};
-// use bevy_mod_outline::{OutlineBundle, OutlineVolume, OutlineMeshExt};
-use crate::top_down_crawler::*;
+use bevy::{prelude::*, sprite::{MaterialMesh2dBundle, Mesh2dHandle}, math::*, render::mesh::shape::Circle};
+use crate::{top_down_crawler::*, easing_functions::Easing, interpolation::*, render_shadows::Elevation};
 
 pub struct FootBodyPartParams {
     pub size: f32,
     pub position: Vec2,
+    pub rotation: Quat,
     
     pub foot_radius: f32,
     pub foot_offset: Vec2,
     pub step_length: f32,
+    // Seconds of the body's motion to place feet ahead by
+    pub step_lead: f32,
+    // How much of the body's turning over that time to anticipate
+    pub turn_lead: f32,
     pub foot_z_index: f32,
-    pub foot_speed: f32,
     pub foot_color: Color,
+
+    pub step_duration: f32,
+    pub step_easing: Easing,
+    pub lift_scale: f32,
+    pub lift_height: f32,
+
+    pub color: Color,
+    pub max_bend: f32,
+    pub leg: Option<LegParams>,
+}
+
+pub struct LegParams {
+    pub upper_length: f32,
+    pub lower_length: f32,
+    // Direction the left knee bends towards in the vertebra's space, mirrored for the right leg
+    pub knee_direction: Vec2,
+    pub width: f32,
 }
 
 pub struct BodyPartParams {
     pub size: f32,
     pub position: Vec2,
+    pub rotation: Quat,
+
+    pub color: Color,
+    // Largest angle in radians this vertebra may turn away from the part in front of it
+    pub max_bend: f32,
 }
 
 pub struct HeadParams {
     pub size: Vec2,
     pub position: Vec2,
+    pub rotation: Quat,
     
     pub turn_speed: f32,
     pub z_index: f32,
@@ -35,6 +61,9 @@ pub struct HeadParams {
 
     pub eye_size: f32,
     pub pupil_size: f32,
+
+    // The body mesh already draws the head, so only its eyes are spawned
+    pub capped: bool,
 }
 
 pub fn spawn_vertebra_feet(
@@ -42,30 +71,36 @@ pub fn spawn_vertebra_feet(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<ColorMaterial>>,
-) {
-    let mut mesh = Mesh::from(Circle::new(params.foot_radius));
-    // mesh.generate_outline_normals().unwrap();
-    // let mesh = meshes.add(shape::Circle::new(params.foot_radius).into());
-    
+) -> Entity {
+    let foot_pos_l = params.position + (params.rotation * vec3(-params.foot_offset.x, 0.0, 0.0)).truncate();
+    let foot_pos_r = params.position + (params.rotation * vec3(params.foot_offset.x, 0.0, 0.0)).truncate();
+
+    let mesh = Mesh::from(Circle::new(params.foot_radius));
 
     let foot_l = commands.spawn((
         MaterialMesh2dBundle {
             mesh: meshes.add(mesh).into(),
             material: materials.add(ColorMaterial::from(params.foot_color)),
-            transform: Transform::from_translation(vec3(
-                -params.foot_offset.x, 
-                params.position.y, 
-                params.foot_z_index
-            )),
+            transform: Transform::from_translation(foot_pos_l.extend(params.foot_z_index)),
             ..default()
         },
+        InterpolatedTransform::new(Transform::from_translation(foot_pos_l.extend(params.foot_z_index))),
         Foot {
             z_index: params.foot_z_index,
-            foot_speed: params.foot_speed,
 
-            target_pos: vec2(-params.foot_offset.x, params.position.y),
+            target_pos: foot_pos_l,
             grounded: true,
+
+            step_start: foot_pos_l,
+            step_time: 0.0,
+            step_duration: params.step_duration,
+            easing: params.step_easing,
+
+            lift: 0.0,
+            lift_scale: params.lift_scale,
+            lift_height: params.lift_height,
         },
+        Elevation::default(),
         
         Name::new("Foot_l"),
     )).id();
@@ -74,53 +109,121 @@ pub fn spawn_vertebra_feet(
         MaterialMesh2dBundle {
             mesh: meshes.add(shape::Circle::new(params.foot_radius).into()).into(),
             material: materials.add(ColorMaterial::from(params.foot_color)),
-            transform: Transform::from_translation(vec3(
-                params.foot_offset.y, 
-                params.position.y, 
-                params.foot_z_index
-            )),
+            transform: Transform::from_translation(foot_pos_r.extend(params.foot_z_index)),
             ..default()
         },
+        InterpolatedTransform::new(Transform::from_translation(foot_pos_r.extend(params.foot_z_index))),
         Foot {
             z_index: params.foot_z_index,
-            foot_speed: params.foot_speed,
 
-            target_pos: vec2(params.foot_offset.x, params.position.y),
+            target_pos: foot_pos_r,
             grounded: true,
+
+            step_start: foot_pos_r,
+            step_time: 0.0,
+            step_duration: params.step_duration,
+            easing: params.step_easing,
+
+            lift: 0.0,
+            lift_scale: params.lift_scale,
+            lift_height: params.lift_height,
         },
+        Elevation::default(),
         Name::new("Foot_r"),
     )).id();
 
+    let (leg_l, leg_r) = match &params.leg {
+        Some(leg) => (
+            Some(spawn_leg(leg, leg.knee_direction, &params, commands, meshes, materials)),
+            Some(spawn_leg(leg, leg.knee_direction * vec2(-1.0, 1.0), &params, commands, meshes, materials)),
+        ),
+        None => (None, None),
+    };
+
+    let transform = Transform::from_translation(params.position.extend(0.0))
+        .with_rotation(params.rotation);
+
     commands.spawn((
-        Transform::from_translation(params.position.extend(0.0)),
+        transform,
+        InterpolatedTransform::new(transform),
         Vertebra {
             foot_offset: params.foot_offset,
             step_length: params.step_length,
+            step_lead: params.step_lead,
+            turn_lead: params.turn_lead,
             width: params.size,
+            width_scale: 1.0,
+            color: params.color,
+            max_bend: params.max_bend,
 
             foot_l: Some(foot_l),
             foot_r: Some(foot_r),
+            leg_l,
+            leg_r,
         },
         Name::new("Vertebra"),
-    ));
+    )).id()
+}
+
+fn spawn_leg(
+    params: &LegParams,
+    knee_direction: Vec2,
+    foot_params: &FootBodyPartParams,
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+) -> Entity {
+    let leg = Leg {
+        upper_length: params.upper_length,
+        lower_length: params.lower_length,
+        knee_direction,
+        width: params.width,
+
+        shoulder: foot_params.position,
+        knee: foot_params.position,
+        ankle: foot_params.position,
+    };
+
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(build_leg_mesh(&leg)).into(),
+            material: materials.add(ColorMaterial::from(foot_params.foot_color)),
+            // Tucked in under both the body and the foot
+            transform: Transform::from_translation(vec3(0.0, 0.0, foot_params.foot_z_index - 0.5)),
+            ..default()
+        },
+        leg,
+        Name::new("Leg"),
+    )).id()
 }
 
 pub fn spawn_vertebra(
     params: BodyPartParams,
     commands: &mut Commands,
-) {
+) -> Entity {
+    let transform = Transform::from_translation(params.position.extend(0.0))
+        .with_rotation(params.rotation);
+
     commands.spawn((
-        Transform::from_translation(params.position.extend(0.0)),
+        transform,
+        InterpolatedTransform::new(transform),
         Vertebra {
             foot_offset: vec2(0.0, 0.0),
             step_length: 1.0,
+            step_lead: 0.0,
+            turn_lead: 0.0,
             width: params.size,
+            width_scale: 1.0,
+            color: params.color,
+            max_bend: params.max_bend,
 
             foot_l: None,
             foot_r: None,
+            leg_l: None,
+            leg_r: None,
         },
         Name::new("Vertebra"),
-    ));
+    )).id()
 }
 
 pub fn spawn_vertebra_many(
@@ -137,7 +240,7 @@ pub fn spawn_head(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<ColorMaterial>>,
-) {
+) -> Entity {
     let eye_black_l = commands.spawn((
         MaterialMesh2dBundle {
             mesh: meshes.add(shape::Circle::new(params.pupil_size).into()).into(),
@@ -192,20 +295,19 @@ pub fn spawn_head(
 
     commands.entity(eye_r).add_child(eye_black_r);
 
+    let head_transform = Transform {
+        translation: vec3(params.position.x, params.position.y, params.z_index),
+        rotation: params.rotation,
+        scale: vec3(1.0, 1.0, 1.0),
+    };
+
     let head = commands.spawn((
-        MaterialMesh2dBundle {
-            mesh: meshes.add(shape::Circle::new(params.size.x * 0.5).into()).into(),
-            material: materials.add(ColorMaterial::from(params.head_color)),
-            transform: Transform {
-                translation: vec3(params.position.x, params.position.y, params.z_index),
-                scale: vec3(1.0, 1.0, 1.0),
-                ..default()
-            },
-            ..default()
-        },
+        SpatialBundle::from_transform(head_transform),
+        InterpolatedTransform::new(head_transform),
         Head {
             z_index: params.z_index,
             neck_width: params.neck_width,
+            color: params.head_color,
         },
         Controllable {
             move_speed: params.move_speed,
@@ -215,6 +317,15 @@ pub fn spawn_head(
         Name::new("Head"),
     )).id();
 
+    if !params.capped {
+        commands.entity(head).insert((
+            Mesh2dHandle(meshes.add(shape::Circle::new(params.size.x * 0.5).into())),
+            materials.add(ColorMaterial::from(params.head_color)),
+        ));
+    }
+
     commands.entity(head).add_child(eye_r);
     commands.entity(head).add_child(eye_l);
-}
\ No newline at end of file
+
+    head
+}
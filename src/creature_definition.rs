@@ -0,0 +1,478 @@
+use bevy::{
+    prelude::*,
+    math::*,
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    render::texture::{ImageAddressMode, ImageLoaderSettings, ImageSampler, ImageSamplerDescriptor},
+    utils::{BoxedFuture, HashSet},
+};
+use std::f32::consts::PI;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{body_animation::*, body_pattern::*, builders::*, easing_functions::Easing, footprints::*, top_down_crawler::*};
+
+// Describes a whole creature so new ones can be authored as
+// `*.creature.ron` files in `assets/creatures` without recompiling.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct CreatureDefinition {
+    pub vertebra_spacing: f32,
+    pub body_color: Color,
+    // Points the body outline is smoothed with between two vertebrae
+    #[serde(default = "default_body_subdivisions")]
+    pub body_subdivisions: usize,
+    // Rounds the body off behind the last vertebra
+    #[serde(default)]
+    pub tail_cap: bool,
+    // How `skin` and `pattern` are laid out along the body
+    #[serde(default)]
+    pub uv_mapping: UvMapping,
+    // Drawn in `body_color` if left out
+    #[serde(default)]
+    pub skin: Option<SkinDefinition>,
+    // Procedural markings, drawn instead of `skin` when both are given
+    #[serde(default)]
+    pub pattern: Option<PatternDefinition>,
+    #[serde(default)]
+    pub breathing: Option<BreathingDefinition>,
+    // Whether the body bulges around whatever the creature swallows
+    #[serde(default)]
+    pub swallows: bool,
+    // Default for how far (in radians) each vertebra may bend, unconstrained if left out
+    #[serde(default = "default_max_bend")]
+    pub max_bend: f32,
+
+    #[serde(default)]
+    pub solver: ChainSolver,
+    #[serde(default)]
+    pub gait: Gait,
+    // Index of a vertebra to pin where it spawns, only honoured by the FABRIK solver
+    #[serde(default)]
+    pub anchored_vertebra: Option<usize>,
+    // Leaves no prints if left out
+    #[serde(default)]
+    pub footprints: Option<FootprintDefinition>,
+
+    pub head: HeadDefinition,
+    // Ordered from the neck to the tip of the tail
+    pub vertebrae: Vec<VertebraDefinition>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HeadDefinition {
+    pub size: Vec2,
+
+    pub turn_speed: f32,
+    pub z_index: f32,
+    pub neck_width: f32,
+
+    pub move_speed: f32,
+
+    pub eye_size: f32,
+    pub pupil_size: f32,
+
+    // Draws the head as part of the body mesh instead of as a separate circle
+    #[serde(default)]
+    pub cap: Option<HeadCapDefinition>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HeadCapDefinition {
+    // 1 is a plain half ellipse, larger values narrow it into a pointier snout
+    #[serde(default = "default_snout")]
+    pub snout: f32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SkinDefinition {
+    // Path of a tiling image in `assets`, repeated along and across the body
+    pub texture: String,
+    // Multiplied with the texture, `body_color` if left out
+    #[serde(default)]
+    pub tint: Option<Color>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct VertebraDefinition {
+    pub width: f32,
+    // Tints the body around this vertebra, blending into its neighbours
+    #[serde(default = "default_vertebra_color")]
+    pub color: Color,
+    // Overrides the creature's `max_bend` for this vertebra
+    #[serde(default)]
+    pub max_bend: Option<f32>,
+    #[serde(default)]
+    pub feet: Option<FeetDefinition>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FeetDefinition {
+    pub foot_radius: f32,
+    pub foot_offset: Vec2,
+    pub step_length: f32,
+    // Feet are placed where the body will be this many seconds from now
+    #[serde(default)]
+    pub step_lead: f32,
+    #[serde(default)]
+    pub turn_lead: f32,
+    pub foot_z_index: f32,
+    pub foot_color: Color,
+
+    // Seconds a single step takes
+    pub step_duration: f32,
+    // Curve for the foot's travel along the ground during a step
+    #[serde(default)]
+    pub step_easing: Easing,
+    // Sprite scale at the top of a step
+    #[serde(default = "default_lift_scale")]
+    pub lift_scale: f32,
+    // Height at the top of a step, which only changes how far the foot's shadow falls
+    #[serde(default)]
+    pub lift_height: f32,
+
+    #[serde(default)]
+    pub leg: Option<LegDefinition>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LegDefinition {
+    pub upper_length: f32,
+    pub lower_length: f32,
+    // Direction the left knee bends towards, with -X pointing out of the body and +Y towards the head.
+    // The right leg mirrors it.
+    pub knee_direction: Vec2,
+    pub width: f32,
+}
+
+fn default_max_bend() -> f32 {
+    PI
+}
+
+fn default_body_subdivisions() -> usize {
+    4
+}
+
+fn default_vertebra_color() -> Color {
+    Color::WHITE
+}
+
+fn default_snout() -> f32 {
+    1.0
+}
+
+fn default_lift_scale() -> f32 {
+    1.0
+}
+
+#[derive(Default)]
+pub struct CreatureDefinitionLoader;
+
+#[derive(Debug, Error)]
+pub enum CreatureDefinitionLoaderError {
+    #[error("Could not read creature definition: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse creature definition: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for CreatureDefinitionLoader {
+    type Asset = CreatureDefinition;
+    type Settings = ();
+    type Error = CreatureDefinitionLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            Ok(ron::de::from_bytes::<CreatureDefinition>(&bytes)?)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["creature.ron"]
+    }
+}
+
+// Spawns the creature described by `definition` once the asset has loaded,
+// with its root at `position` and facing along `rotation`.
+// The entity holding this becomes the creature's root.
+#[derive(Component)]
+pub struct CreatureSpawn {
+    pub definition: Handle<CreatureDefinition>,
+    pub position: Vec2,
+    pub rotation: Quat,
+    // Lifts the body over creatures with a lower depth, see `BodyShape::depth`. Keep it below 1.
+    pub depth: f32,
+    // Where the definition's `anchored_vertebra` is pinned, straight behind `position` if left out
+    pub anchor: Option<Vec2>,
+}
+
+// Remembers which definition a creature was built from so it can be rebuilt when the file changes
+#[derive(Component)]
+pub struct CreatureSource(pub Handle<CreatureDefinition>);
+
+pub fn spawn_creatures_from_definitions(
+    spawn_q: Query<(Entity, &CreatureSpawn)>,
+    definitions: Res<Assets<CreatureDefinition>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut pattern_materials: ResMut<Assets<BodyPatternMaterial>>,
+) {
+    for (entity, spawn) in spawn_q.iter() {
+        let Some(definition) = definitions.get(&spawn.definition) else { continue; };
+
+        let (creature, spine) = spawn_from_definition(
+            definition,
+            spawn.position,
+            spawn.rotation,
+            spawn.depth,
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+        );
+
+        // Pinned by entity, so the anchor stays on the same vertebra when others are removed
+        let anchor = definition.anchored_vertebra
+            .and_then(|index| spine.vertebrae.get(index).map(|vertebra| (index, *vertebra)))
+            .map(|(index, vertebra)| SpineAnchor {
+                vertebra,
+                position: spawn.anchor.unwrap_or_else(|| spawn.position 
+                    + (spawn.rotation * vec3(0.0, -(index as f32) * definition.vertebra_spacing, 0.0)).truncate()),
+            });
+
+        commands.entity(entity)
+            .remove::<CreatureSpawn>()
+            .insert((
+                creature, 
+                spine, 
+                definition.solver, 
+                definition.gait.clone(), 
+                GaitState::default(),
+                CreatureSource(spawn.definition.clone()),
+            ));
+
+        if let Some(anchor) = anchor {
+            commands.entity(entity).insert(anchor);
+        }
+
+        if let Some(skin) = &definition.skin {
+            commands.entity(entity).insert(BodySkin {
+                texture: load_skin_texture(&asset_server, &skin.texture),
+                tint: skin.tint.unwrap_or(definition.body_color),
+            });
+        }
+
+        if let Some(pattern) = &definition.pattern {
+            let seed = rand::random::<f32>() * 1000.0;
+
+            commands.entity(entity).insert(BodyPatternSkin(pattern_materials.add(BodyPatternMaterial::new(pattern, seed))));
+        }
+
+        if definition.swallows {
+            commands.entity(entity).insert(Lumps::default());
+        }
+
+        if let Some(breathing) = &definition.breathing {
+            commands.entity(entity).insert(Breathing::from(breathing));
+        }
+
+        if let Some(footprints) = &definition.footprints {
+            commands.entity(entity).insert(FootprintTrail::new(footprints, &mut meshes, &mut materials));
+        }
+    }
+}
+
+pub fn reload_creature_definitions(
+    mut definition_events: EventReader<AssetEvent<CreatureDefinition>>,
+    definitions: Res<Assets<CreatureDefinition>>,
+    creature_q: Query<(Entity, &Creature, &Spine, &CreatureSource, Option<&SpineAnchor>)>,
+    head_q: Query<&Transform, With<Head>>,
+    vertebra_q: Query<&Vertebra>,
+    mut commands: Commands,
+) {
+    let modified: HashSet<AssetId<CreatureDefinition>> = definition_events.read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    if modified.is_empty() { return; }
+
+    for (entity, creature, spine, source, anchor) in creature_q.iter() {
+        if !modified.contains(&source.0.id()) { continue; }
+        let Some(definition) = definitions.get(&source.0) else { continue; };
+        let Ok(head_transform) = head_q.get(creature.head) else { continue; };
+
+        // Place the new root so the head ends up exactly where the old one was
+        let rotation = head_transform.rotation;
+        let position = head_transform.translation.truncate()
+            - (rotation * vec3(0.0, definition.vertebra_spacing, 0.0)).truncate();
+
+        despawn_creature_parts(creature, spine, &vertebra_q, &mut commands);
+
+        commands.entity(entity)
+            .remove::<(Creature, Spine, ChainSolver, Gait, GaitState, SpineAnchor, FootprintTrail, BodySkin, BodyPatternSkin)>()
+            .remove::<(Breathing, Lumps)>()
+            .insert(CreatureSpawn {
+                definition: source.0.clone(),
+                position,
+                rotation,
+                depth: creature.body_shape.depth,
+                // A pinned vertebra stays wherever it was pinned instead of snapping behind the head
+                anchor: anchor.map(|anchor| anchor.position),
+            });
+    }
+}
+
+// Skins tile along the whole body, so their textures need to repeat instead of clamping at the edges
+fn load_skin_texture(asset_server: &AssetServer, path: &str) -> Handle<Image> {
+    asset_server.load_with_settings(path.to_owned(), |settings: &mut ImageLoaderSettings| {
+        settings.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+            address_mode_u: ImageAddressMode::Repeat,
+            address_mode_v: ImageAddressMode::Repeat,
+            ..ImageSamplerDescriptor::nearest()
+        });
+    })
+}
+
+// Despawns everything a creature owns, leaving the root entity itself alone
+pub fn despawn_creature_parts(
+    creature: &Creature,
+    spine: &Spine,
+    vertebra_q: &Query<&Vertebra>,
+    commands: &mut Commands,
+) {
+    commands.entity(creature.head).despawn_recursive();
+
+    for vertebra_entity in spine.vertebrae.iter() {
+        if let Ok(vertebra) = vertebra_q.get(*vertebra_entity) {
+            for part in [vertebra.foot_l, vertebra.foot_r, vertebra.leg_l, vertebra.leg_r].into_iter().flatten() {
+                commands.entity(part).despawn_recursive();
+            }
+        }
+        commands.entity(*vertebra_entity).despawn_recursive();
+    }
+
+    if let Some(body_mesh) = creature.body_mesh {
+        commands.entity(body_mesh).despawn_recursive();
+    }
+}
+
+pub fn spawn_from_definition(
+    definition: &CreatureDefinition,
+    position: Vec2,
+    rotation: Quat,
+    depth: f32,
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+) -> (Creature, Spine) {
+    let spacing = definition.vertebra_spacing;
+    let head = &definition.head;
+    let along_spine = |dist: f32| position + (rotation * vec3(0.0, dist, 0.0)).truncate();
+
+    let head_entity = spawn_head(
+        HeadParams {
+            size: head.size,
+            position: along_spine(spacing),
+            rotation,
+
+            turn_speed: head.turn_speed,
+            z_index: head.z_index,
+            neck_width: head.neck_width,
+
+            vertebra_dist: spacing,
+            move_speed: head.move_speed,
+            head_color: definition.body_color,
+
+            eye_size: head.eye_size,
+            pupil_size: head.pupil_size,
+
+            capped: head.cap.is_some(),
+        },
+        commands,
+        meshes,
+        materials,
+    );
+
+    let mut vertebrae = Vec::with_capacity(definition.vertebrae.len());
+
+    for (i, vertebra) in definition.vertebrae.iter().enumerate() {
+        let vertebra_position = along_spine(-(i as f32) * spacing);
+        let max_bend = vertebra.max_bend.unwrap_or(definition.max_bend);
+
+        let vertebra_entity = match &vertebra.feet {
+            Some(feet) => spawn_vertebra_feet(
+                FootBodyPartParams {
+                    size: vertebra.width,
+                    position: vertebra_position,
+                    rotation,
+
+                    foot_radius: feet.foot_radius,
+                    foot_offset: feet.foot_offset,
+                    step_length: feet.step_length,
+                    step_lead: feet.step_lead,
+                    turn_lead: feet.turn_lead,
+                    foot_z_index: feet.foot_z_index,
+                    foot_color: feet.foot_color,
+
+                    step_duration: feet.step_duration,
+                    step_easing: feet.step_easing,
+                    lift_scale: feet.lift_scale,
+                    lift_height: feet.lift_height,
+
+                    color: vertebra.color,
+                    max_bend,
+                    leg: feet.leg.as_ref().map(|leg| LegParams {
+                        upper_length: leg.upper_length,
+                        lower_length: leg.lower_length,
+                        knee_direction: leg.knee_direction,
+                        width: leg.width,
+                    }),
+                },
+                commands,
+                meshes,
+                materials,
+            ),
+            None => spawn_vertebra(
+                BodyPartParams {
+                    size: vertebra.width,
+                    position: vertebra_position,
+                    rotation,
+
+                    color: vertebra.color,
+                    max_bend,
+                },
+                commands,
+            ),
+        };
+
+        vertebrae.push(vertebra_entity);
+    }
+
+    (
+        Creature {
+            head: head_entity,
+            body_mesh: None,
+            body_shape: BodyShape {
+                subdivisions: definition.body_subdivisions.max(1),
+                head_cap: head.cap.as_ref().map(|cap| HeadCap {
+                    size: head.size,
+                    snout: cap.snout,
+                }),
+                tail_cap: definition.tail_cap,
+                uv_mapping: definition.uv_mapping,
+                depth,
+            },
+        },
+        Spine { vertebrae },
+    )
+}
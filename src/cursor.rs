@@ -2,6 +2,8 @@
 
 use bevy::{prelude::*, window::PrimaryWindow, math::vec2};
 
+use crate::render_shadows::RenderTexView;
+
 pub struct CursorPlugin;
 
 impl Plugin for CursorPlugin {
@@ -36,19 +38,20 @@ fn update_cursor_sprite (
 fn set_cursor_world_pos(
     mut cursor_world_pos: ResMut<CursorWorldPos>,
     window_q: Query<&Window, With<PrimaryWindow>>,
-    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    render_tex_view: Res<RenderTexView>,
 ) {
-    let (camera, camera_transform) = camera_q.get_single()
-        .expect("Exactly one Main Camera was not found");
+    // The world is drawn by the low-res camera, the window only shows its render texture scaled up
+    let (camera, camera_transform) = camera_q.get(render_tex_view.camera)
+        .expect("The render texture camera was not found");
     let window = window_q.get_single()
         .expect("Exactly one window was not found");
 
     if let Some(mouse_pos) = window.cursor_position()
-        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
-        .map(|ray| ray.origin.truncate()
+        .map(|cursor| render_tex_view.window_to_texture(cursor))
+        .and_then(|pixel| camera.viewport_to_world_2d(camera_transform, pixel)
     ) {
-        // Stupid simple fix lol
-        cursor_world_pos.0 = mouse_pos / 4.0;
+        cursor_world_pos.0 = mouse_pos;
     }    
 }
 
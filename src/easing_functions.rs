@@ -2,6 +2,35 @@
 
 use std::f32::consts::PI;
 
+use serde::Deserialize;
+
+// Picks one of the curves below by name, e.g. from an asset file
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+pub enum Easing {
+    Linear,
+    SineIn,
+    SineOut,
+    #[default]
+    SineInOut,
+    BackIn,
+    BackOut,
+    BackInOut,
+}
+
+impl Easing {
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::SineIn => sine_in(t),
+            Easing::SineOut => sine_out(t),
+            Easing::SineInOut => sine_in_out(t),
+            Easing::BackIn => back_in(t),
+            Easing::BackOut => back_out(t),
+            Easing::BackInOut => back_in_out(t),
+        }
+    }
+}
+
 pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
     (1.0 - t) * a + t * b
 }
@@ -19,7 +48,7 @@ pub fn sine_out(t: f32) -> f32 {
 }
 
 pub fn sine_in_out(t: f32) -> f32 {
-    -((PI * t - 1.0) * 0.5).cos()
+    -((PI * t).cos() - 1.0) * 0.5
 }
 
 pub fn back_in(t: f32) -> f32 {
@@ -46,3 +75,45 @@ pub fn back_in_out(t: f32) -> f32 {
         ((2.0 * t - 2.0).powf(2.0) * ((c2 + 1.0) * (t * 2.0 - 2.0) + c2) + 2.0) * 0.5
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    const ALL: [Easing; 7] = [
+        Easing::Linear,
+        Easing::SineIn,
+        Easing::SineOut,
+        Easing::SineInOut,
+        Easing::BackIn,
+        Easing::BackOut,
+        Easing::BackInOut,
+    ];
+
+    #[test]
+    fn easings_start_at_zero_and_end_at_one() {
+        for easing in ALL {
+            assert!(easing.apply(0.0).abs() < EPSILON, "{easing:?} starts at {}", easing.apply(0.0));
+            assert!((easing.apply(1.0) - 1.0).abs() < EPSILON, "{easing:?} ends at {}", easing.apply(1.0));
+        }
+    }
+
+    #[test]
+    fn symmetric_easings_are_halfway_at_half_time() {
+        for easing in [Easing::Linear, Easing::SineInOut, Easing::BackInOut] {
+            assert!((easing.apply(0.5) - 0.5).abs() < EPSILON, "{easing:?} is at {} halfway", easing.apply(0.5));
+        }
+    }
+
+    #[test]
+    fn in_and_out_easings_mirror_each_other() {
+        // Neither is halfway at half time, but together they cover exactly the whole distance
+        for (ease_in, ease_out) in [(Easing::SineIn, Easing::SineOut), (Easing::BackIn, Easing::BackOut)] {
+            for t in [0.0, 0.25, 0.5, 0.75, 1.0] {
+                assert!((ease_in.apply(t) + ease_out.apply(1.0 - t) - 1.0).abs() < EPSILON);
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// Fading prints left wherever a creature plants a foot, drawn on the ground layer
+// so they sit under the shadows instead of casting their own.
+
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, sprite::{MaterialMesh2dBundle, Mesh2dHandle}, render::view::RenderLayers};
+use serde::Deserialize;
+
+use crate::{top_down_crawler::*, easing_functions::Easing, render_shadows::GroundLayer};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FootprintDefinition {
+    // Width and length of a single print
+    pub size: Vec2,
+    pub color: Color,
+    // Seconds a print takes to fade out completely
+    pub lifetime: f32,
+    // Curve for the fade, from fully visible at 0 to gone at 1
+    #[serde(default)]
+    pub fade: Easing,
+    // Oldest prints are removed first once a creature has left this many
+    pub max_count: usize,
+}
+
+// Shades a print steps through while fading, shared by all prints of a trail
+const FADE_STEPS: usize = 8;
+
+// Lives on a creature's root next to `Creature`, keeping track of the prints it left
+#[derive(Component)]
+pub struct FootprintTrail {
+    pub size: Vec2,
+    pub lifetime: f32,
+    pub fade: Easing,
+    pub max_count: usize,
+
+    pub mesh: Mesh2dHandle,
+    // From barely visible to the full print color, one per fade step
+    pub materials: Vec<Handle<ColorMaterial>>,
+    // Oldest first
+    pub prints: VecDeque<Entity>,
+}
+
+impl FootprintTrail {
+    pub fn new(
+        definition: &FootprintDefinition,
+        meshes: &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<ColorMaterial>>,
+    ) -> Self {
+        Self {
+            size: definition.size,
+            lifetime: definition.lifetime,
+            fade: definition.fade,
+            max_count: definition.max_count,
+
+            // Unit circle, stretched into an oval by each print's scale
+            mesh: meshes.add(shape::Circle::new(0.5).into()).into(),
+            materials: (1..=FADE_STEPS)
+                .map(|step| {
+                    let alpha = definition.color.a() * step as f32 / FADE_STEPS as f32;
+                    materials.add(ColorMaterial::from(definition.color.with_a(alpha)))
+                })
+                .collect(),
+            prints: VecDeque::new(),
+        }
+    }
+
+    // Material for a print with `visibility` left, from 1 when fresh to 0 when gone
+    fn material(&self, visibility: f32) -> Handle<ColorMaterial> {
+        let step = (visibility.clamp(0.0, 1.0) * FADE_STEPS as f32).ceil() as usize;
+        self.materials[step.clamp(1, FADE_STEPS) - 1].clone()
+    }
+}
+
+#[derive(Component)]
+pub struct Footprint {
+    pub age: f32,
+    pub lifetime: f32,
+    pub fade: Easing,
+    // Root of the creature that left the print, whose `FootprintTrail` holds its materials
+    pub trail: Entity,
+}
+
+pub fn spawn_footprints(
+    mut planted_events: EventReader<FootPlanted>,
+    mut trail_q: Query<(&Spine, &mut FootprintTrail)>,
+    vertebra_q: Query<(&Transform, &Vertebra)>,
+    ground_layer: Option<Res<GroundLayer>>,
+    mut commands: Commands,
+) {
+    let layer = ground_layer.map_or(RenderLayers::default(), |layer| RenderLayers::layer(**layer));
+
+    for event in planted_events.read() {
+        let Ok((spine, mut trail)) = trail_q.get_mut(event.creature) else { continue; };
+
+        // Prints point the way the vertebra the foot belongs to is facing
+        let Some((vertebra_transform, _)) = vertebra_q.iter_many(&spine.vertebrae)
+            .find(|(_, vertebra)| vertebra.foot_l == Some(event.foot) || vertebra.foot_r == Some(event.foot))
+        else { continue; };
+
+        let print = commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: trail.mesh.clone(),
+                material: trail.material(1.0),
+                transform: Transform {
+                    translation: event.position.extend(0.0),
+                    rotation: vertebra_transform.rotation,
+                    scale: trail.size.extend(1.0),
+                },
+                ..default()
+            },
+            Footprint {
+                age: 0.0,
+                lifetime: trail.lifetime,
+                fade: trail.fade,
+                trail: event.creature,
+            },
+            layer,
+            Name::new("Footprint"),
+        )).id();
+
+        trail.prints.push_back(print);
+
+        while trail.prints.len() > trail.max_count {
+            let Some(oldest) = trail.prints.pop_front() else { break; };
+            if let Some(entity) = commands.get_entity(oldest) {
+                entity.despawn_recursive();
+            }
+        }
+    }
+}
+
+// Ages every print and steps it through its trail's shared materials, so no material
+// has to change while prints fade
+pub fn fade_footprints(
+    mut footprint_q: Query<(Entity, &mut Footprint, &mut Handle<ColorMaterial>)>,
+    mut trail_q: Query<&mut FootprintTrail>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for (entity, mut footprint, mut material) in footprint_q.iter_mut() {
+        footprint.age += time.delta_seconds();
+
+        let t = if footprint.lifetime > 0.0 { footprint.age / footprint.lifetime } else { 1.0 };
+
+        if t >= 1.0 {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        if let Ok(trail) = trail_q.get(footprint.trail) {
+            let faded = trail.material(1.0 - footprint.fade.apply(t));
+            material.set_if_neq(faded);
+        }
+    }
+
+    // Forget prints that faded out on their own
+    for mut trail in trail_q.iter_mut() {
+        trail.prints.retain(|print| footprint_q.get(*print).is_ok_and(|(_, footprint, _)| footprint.age < footprint.lifetime));
+    }
+}
@@ -0,0 +1,72 @@
+// Analytic inverse kinematics for limbs. Like `spine`, this stays free of ECS types.
+
+use bevy::math::*;
+
+// Two-bone IK for a limb rooted at `root` reaching for `target`. Returns the knee and the
+// end of the limb, with the knee bent towards the side `pole` points to. Targets out of
+// reach leave the limb fully stretched towards them.
+pub fn solve_two_bone(root: Vec2, target: Vec2, upper: f32, lower: f32, pole: Vec2) -> (Vec2, Vec2) {
+    let to_target = target - root;
+    let dir = to_target.try_normalize()
+        .or(pole.try_normalize())
+        .unwrap_or(Vec2::Y);
+
+    // Keep the distance away from zero so the law of cosines below stays finite
+    let dist = to_target.length().clamp((upper - lower).abs().max(1e-4), upper + lower);
+
+    let cos_root = (upper * upper + dist * dist - lower * lower) / (2.0 * upper * dist);
+    let root_angle = cos_root.clamp(-1.0, 1.0).acos();
+    let side = if dir.perp_dot(pole) >= 0.0 { 1.0 } else { -1.0 };
+
+    let knee = root + Vec2::from_angle(root_angle * side).rotate(dir) * upper;
+    let end = root + dir * dist;
+
+    (knee, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    #[test]
+    fn two_bone_keeps_bone_lengths_and_reaches_target() {
+        let root = vec2(1.0, 2.0);
+        let target = vec2(8.0, -3.0);
+
+        let (knee, end) = solve_two_bone(root, target, 6.0, 5.0, Vec2::Y);
+
+        assert!((knee.distance(root) - 6.0).abs() < EPSILON);
+        assert!((end.distance(knee) - 5.0).abs() < EPSILON);
+        assert!(end.distance(target) < EPSILON);
+    }
+
+    #[test]
+    fn two_bone_bends_towards_pole() {
+        let target = vec2(8.0, 0.0);
+
+        let (knee_up, _) = solve_two_bone(Vec2::ZERO, target, 5.0, 5.0, Vec2::Y);
+        let (knee_down, _) = solve_two_bone(Vec2::ZERO, target, 5.0, 5.0, Vec2::NEG_Y);
+
+        assert!(knee_up.y > 0.0);
+        assert!(knee_down.y < 0.0);
+    }
+
+    #[test]
+    fn two_bone_stretches_towards_unreachable_target() {
+        let (knee, end) = solve_two_bone(Vec2::ZERO, vec2(0.0, 50.0), 5.0, 5.0, Vec2::X);
+
+        assert!(knee.distance(vec2(0.0, 5.0)) < EPSILON);
+        assert!(end.distance(vec2(0.0, 10.0)) < EPSILON);
+    }
+
+    #[test]
+    fn two_bone_handles_target_on_root() {
+        let (knee, end) = solve_two_bone(Vec2::ZERO, Vec2::ZERO, 5.0, 5.0, Vec2::X);
+
+        assert!(knee.is_finite());
+        assert!(end.is_finite());
+        assert!((knee.length() - 5.0).abs() < EPSILON);
+    }
+}
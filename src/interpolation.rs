@@ -0,0 +1,58 @@
+// Smooths out parts that are simulated in `FixedUpdate`, so they render at the
+// display frame rate without the simulation depending on it.
+
+use bevy::prelude::*;
+
+// The last two simulated states of an entity. Its `Transform` holds the simulated state
+// while `FixedUpdate` runs and an interpolation between these two the rest of the frame.
+#[derive(Component)]
+pub struct InterpolatedTransform {
+    pub previous: Transform,
+    pub current: Transform,
+}
+
+impl InterpolatedTransform {
+    pub fn new(transform: Transform) -> Self {
+        Self {
+            previous: transform,
+            current: transform,
+        }
+    }
+}
+
+// Runs first in every fixed tick to hand the simulation back its own state
+pub fn restore_simulated_transforms(
+    mut interpolated_q: Query<(&mut Transform, &mut InterpolatedTransform)>,
+) {
+    for (mut transform, mut interpolated) in interpolated_q.iter_mut() {
+        interpolated.previous = interpolated.current;
+        // Leave parts that did not move untouched, so change detection can skip them
+        transform.set_if_neq(interpolated.current);
+    }
+}
+
+// Runs last in every fixed tick
+pub fn store_simulated_transforms(
+    mut interpolated_q: Query<(&Transform, &mut InterpolatedTransform)>,
+) {
+    for (transform, mut interpolated) in interpolated_q.iter_mut() {
+        interpolated.current = *transform;
+    }
+}
+
+pub fn interpolate_transforms(
+    mut interpolated_q: Query<(&mut Transform, &InterpolatedTransform)>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    let t = fixed_time.overstep_percentage();
+
+    for (mut transform, interpolated) in interpolated_q.iter_mut() {
+        let (previous, current) = (interpolated.previous, interpolated.current);
+
+        transform.set_if_neq(Transform {
+            translation: previous.translation.lerp(current.translation, t),
+            rotation: previous.rotation.slerp(current.rotation, t),
+            scale: previous.scale.lerp(current.scale, t),
+        });
+    }
+}
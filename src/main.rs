@@ -1,20 +1,30 @@
+use std::f32::consts::PI;
+
 use bevy::{prelude::*, sprite::MaterialMesh2dBundle, render::view::RenderLayers, window::WindowResolution};
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
-// use bevy_mod_outline::*;
-// use bevy_simple_2d_outline::*;
 
 pub mod top_down_crawler;
 pub mod cursor;
 pub mod easing_functions;
 pub mod builders;
 pub mod render_shadows;
+pub mod creature_definition;
+pub mod spine;
+pub mod spline;
+pub mod interpolation;
+pub mod ik;
+pub mod footprints;
+pub mod body_pattern;
+pub mod body_animation;
 // stupid comment
 use top_down_crawler::*;
 use cursor::*;
 use render_shadows::*;
+use body_animation::*;
 
 const BACKGROUND_COLOR: Color = Color::rgb(0.75, 0.9, 0.8);
 const SHADOW_COLOR: Color = Color::rgb(0.0, 0.1, 0.1);
+const OUTLINE_COLOR: Color = Color::rgb(0.05, 0.2, 0.15);
 
 fn main() {
     App::new()
@@ -32,23 +42,34 @@ fn main() {
             .set(ImagePlugin::default_nearest()),
             
             ShadowRenderTexturePlugin {
-                pixel_scale_factor: 4.0,
+                pixel_scale_factor: 4,
 
                 background_color: BACKGROUND_COLOR,
                 shadow_color: SHADOW_COLOR,
+                shadow: ShadowSettings {
+                    angle: -PI * 0.25,
+                    distance: 3.5,
+                    blur: 0,
+                    height_scale: 0.5,
+                },
+
+                outline_color: OUTLINE_COLOR,
+                outline_thickness: 1,
 
-                screen_width: 1200,
-                screen_height: 800,
-                
                 render_layer_index: 1,
+                ground_layer_index: 2,
+                height_layer_index: 3,
+            },
+            TopDownCrawlerPlugin {
+                tick_rate: 64.0,
             },
-            TopDownCrawlerPlugin,
             CursorPlugin,
             // WorldInspectorPlugin::default(),
         ))
         .insert_resource(ClearColor(BACKGROUND_COLOR))
         .insert_resource(Msaa::Off)
         .add_systems(Startup, setup)
+        .add_systems(Update, swallow_on_key.before(animate_body_width))
         .run();
 }
 
@@ -74,3 +95,18 @@ fn setup(
         CustomCursor,
     ));
 }
+
+// Space makes the controlled creature swallow something
+fn swallow_on_key(
+    mut creature_q: Query<(&Creature, &mut Lumps)>,
+    controllable_q: Query<(), With<Controllable>>,
+    keys: Res<Input<KeyCode>>,
+) {
+    if !keys.just_pressed(KeyCode::Space) { return; }
+
+    for (creature, mut lumps) in creature_q.iter_mut() {
+        if !controllable_q.contains(creature.head) { continue; }
+
+        lumps.swallow(0.6, 3.0, 4.0);
+    }
+}
@@ -1,12 +1,34 @@
 use bevy::{
     prelude::*,
+    core_pipeline::clear_color::ClearColorConfig,
     render::{camera::*, render_resource::*, view::RenderLayers},
-    math::*, sprite::{MaterialMesh2dBundle, Material2d, Material2dPlugin},
+    math::*, sprite::{MaterialMesh2dBundle, Material2d, Material2dPlugin, Mesh2dHandle},
+    window::{PrimaryWindow, WindowResized},
 };
 
 #[derive(Resource, Deref, DerefMut)]
 pub struct RenderTexLayer(u8);
 
+// Layer for decals lying flat on the ground, which neither cast nor hide shadows
+#[derive(Resource, Deref, DerefMut)]
+pub struct GroundLayer(u8);
+
+// Layer the heights of elevated parts are drawn to, see `Elevation`
+#[derive(Resource, Deref, DerefMut)]
+pub struct HeightLayer(u8);
+
+// Highest elevation the height texture can tell apart, anything above is clamped
+pub const MAX_ELEVATION: f32 = 64.0;
+
+// How far above the ground a part is drawn, in world units. Parts without one stand on the ground.
+// Anything with a mesh and an `Elevation` casts its shadow that much farther away.
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct Elevation(pub f32);
+
+// Copy of an elevated part's mesh, drawn into the height texture in a shade of its elevation
+#[derive(Component)]
+struct HeightProxy;
+
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
 pub struct CustomMaterial {
     #[uniform(0)]
@@ -16,8 +38,23 @@ pub struct CustomMaterial {
     #[texture(2)]
     #[sampler(3)]
     screen_texture: Option<Handle<Image>>,
+    // How far the shadow falls, in pixels of the render texture with +Y pointing down
     #[uniform(4)]
     shadow_offset: Vec2,
+    #[texture(5)]
+    #[sampler(6)]
+    ground_texture: Option<Handle<Image>>,
+    #[uniform(7)]
+    outline_color: Color,
+    #[uniform(8)]
+    outline_thickness: u32,
+    #[uniform(9)]
+    shadow_blur: u32,
+    #[texture(10)]
+    #[sampler(11)]
+    height_texture: Option<Handle<Image>>,
+    #[uniform(12)]
+    shadow_height_scale: f32,
 }
 
 impl Material2d for CustomMaterial {
@@ -26,31 +63,90 @@ impl Material2d for CustomMaterial {
     }
 }
 
+// Where shadows fall and how soft they are. Change it at any time and the shadow pass follows.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct ShadowSettings {
+    // Direction the shadows fall in, in radians counterclockwise from +X
+    pub angle: f32,
+    // In pixels of the render texture
+    pub distance: f32,
+    // Radius in pixels to soften the shadow edges over, 0 for hard edges
+    pub blur: u32,
+    // Extra pixels of distance for every unit of `Elevation`
+    pub height_scale: f32,
+}
+
+impl ShadowSettings {
+    fn offset(&self) -> Vec2 {
+        // The render texture's Y axis points down, the world's points up
+        Vec2::from_angle(self.angle) * vec2(1.0, -1.0) * self.distance
+    }
+}
+
+#[derive(Resource)]
+struct ShadowMaterial(Handle<CustomMaterial>);
+
+// Size of the low-res render textures, following the primary window
 #[derive(Resource)]
 pub struct ScreenDimensions {
     pub width: u32,
     pub height: u32,
 }
 
+// How the render texture is laid over the window, for mapping window positions into the world.
+// Change `pixel_scale_factor` at any time and the render textures are refitted.
+#[derive(Resource, Debug)]
+pub struct RenderTexView {
+    // Window pixels per render texture pixel
+    pub pixel_scale_factor: u32,
+    // Camera drawing the world into the render texture
+    pub camera: Entity,
+    // Top-left corner of the render texture in the window, past any letterbox bars
+    origin: Vec2,
+}
+
+impl RenderTexView {
+    // Logical window position to a position in the render texture, ready for `camera`'s `viewport_to_world_2d`
+    pub fn window_to_texture(&self, position: Vec2) -> Vec2 {
+        (position - self.origin) / self.pixel_scale_factor.max(1) as f32
+    }
+}
+
+// Everything that has to be refitted when the window changes size
+#[derive(Resource)]
+struct RenderTargets {
+    images: [Handle<Image>; 3],
+    material: Handle<CustomMaterial>,
+    quad: Entity,
+}
+
 pub struct ShadowRenderTexturePlugin {
-    pub pixel_scale_factor: f32,
+    // Window pixels per render texture pixel
+    pub pixel_scale_factor: u32,
     
     pub background_color: Color,
     pub shadow_color: Color,
+    // Starting point for the `ShadowSettings` resource
+    pub shadow: ShadowSettings,
 
-    pub screen_width: u32,
-    pub screen_height: u32,
-    
-    pub render_layer_index: u8
+    // Drawn around everything that is not background, in pixels of the render texture.
+    // 0 turns the outline off.
+    pub outline_color: Color,
+    pub outline_thickness: u32,
+
+    pub render_layer_index: u8,
+    pub ground_layer_index: u8,
+    pub height_layer_index: u8,
 }
 
 impl Plugin for ShadowRenderTexturePlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(Material2dPlugin::<CustomMaterial>::default());
         
+        // Sized to the window by `fit_render_targets` before the first frame
         let i_size = Extent3d {
-            width: self.screen_width / (self.pixel_scale_factor as u32), 
-            height: self.screen_height / (self.pixel_scale_factor as u32), 
+            width: 1,
+            height: 1,
             ..default()
         };
         
@@ -74,9 +170,46 @@ impl Plugin for ShadowRenderTexturePlugin {
     
         let mut images = app.world.get_resource_mut::<Assets<Image>>().unwrap();
         
+        let ground_image_handle = images.add(image.clone());
+        let height_image_handle = images.add(image.clone());
         let image_handle = images.add(image);
-    
+        let image_handles = [image_handle.clone(), ground_image_handle.clone(), height_image_handle.clone()];
+
         app.world.spawn((
+            Camera2dBundle {
+                camera: Camera {
+                    order: -3,
+                    target: RenderTarget::Image(height_image_handle.clone()),
+                    ..default()
+                },
+                camera_2d: Camera2d {
+                    // Black is the ground itself
+                    clear_color: ClearColorConfig::Custom(Color::BLACK),
+                },
+                transform: Transform::from_xyz(0.0, 0.0, 0.0),
+                ..default()
+            },
+            RenderLayers::layer(self.height_layer_index),
+        ));
+
+        app.world.spawn((
+            Camera2dBundle {
+                camera: Camera {
+                    // Render before everything standing on the ground
+                    order: -2,
+                    target: RenderTarget::Image(ground_image_handle.clone()),
+                    ..default()
+                },
+                camera_2d: Camera2d {
+                    clear_color: ClearColorConfig::Custom(self.background_color),
+                },
+                transform: Transform::from_xyz(0.0, 0.0, 0.0),
+                ..default()
+            },
+            RenderLayers::layer(self.ground_layer_index),
+        ));
+    
+        let render_tex_camera = app.world.spawn((
             Camera2dBundle {
                 camera: Camera {
                     // Render before Render Tex camera
@@ -87,32 +220,168 @@ impl Plugin for ShadowRenderTexturePlugin {
                 transform: Transform::from_xyz(0.0, 0.0, 0.0),
                 ..default()
             },
-        ));
+        )).id();
     
         let mut meshes = app.world.get_resource_mut::<Assets<Mesh>>().unwrap();
-        let mesh_handle = meshes.add(shape::Quad::new(vec2(self.screen_width as f32, self.screen_height as f32)).into()).into();
+        // Stretched over the render texture's footprint on screen through its scale
+        let mesh_handle = meshes.add(shape::Quad::new(Vec2::ONE).into()).into();
 
         let mut custom_materials = app.world.get_resource_mut::<Assets<CustomMaterial>>().unwrap();
         let custom_material_handle = custom_materials.add(CustomMaterial {
             background_color: self.background_color,
             shadow_color: self.shadow_color,
             screen_texture: Some(image_handle),
-            shadow_offset: vec2(
-                -10.0 / (self.screen_width as f32), 
-                -10.0 / (self.screen_height as f32)
-            ),
+            shadow_offset: self.shadow.offset(),
+            ground_texture: Some(ground_image_handle),
+            outline_color: self.outline_color,
+            outline_thickness: self.outline_thickness,
+            shadow_blur: self.shadow.blur,
+            height_texture: Some(height_image_handle),
+            shadow_height_scale: self.shadow.height_scale,
         });
 
-        app.world.spawn((
+        let quad = app.world.spawn((
             MaterialMesh2dBundle {
                 mesh: mesh_handle,
-                material: custom_material_handle,
-                transform: Transform::from_scale(vec3(self.pixel_scale_factor, self.pixel_scale_factor, 1.0)),
+                material: custom_material_handle.clone(),
                 ..default()
             },
             RenderLayers::layer(self.render_layer_index)
-        ));
+        )).id();
     
         app.insert_resource(RenderTexLayer(self.render_layer_index));
+        app.insert_resource(GroundLayer(self.ground_layer_index));
+        app.insert_resource(HeightLayer(self.height_layer_index));
+        app.insert_resource(self.shadow);
+        app.insert_resource(ShadowMaterial(custom_material_handle.clone()));
+        app.insert_resource(ScreenDimensions { width: 0, height: 0 });
+        app.insert_resource(RenderTexView {
+            pixel_scale_factor: self.pixel_scale_factor,
+            camera: render_tex_camera,
+            origin: Vec2::ZERO,
+        });
+        app.insert_resource(RenderTargets {
+            images: image_handles,
+            material: custom_material_handle,
+            quad,
+        });
+
+        app.add_systems(Startup, fit_render_targets);
+        app.add_systems(Update, (
+            fit_render_targets.run_if(on_event::<WindowResized>().or_else(resource_changed::<RenderTexView>())),
+            apply_shadow_settings,
+            spawn_height_proxies,
+            update_height_proxies,
+        ));
+    }
+}
+
+fn spawn_height_proxies(
+    elevated_q: Query<(Entity, &Mesh2dHandle, &Elevation), Added<Elevation>>,
+    height_layer: Res<HeightLayer>,
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (entity, mesh, elevation) in elevated_q.iter() {
+        let proxy = commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: mesh.clone(),
+                material: materials.add(ColorMaterial::from(height_color(elevation.0))),
+                visibility: if elevation.0 > 0.0 { Visibility::Inherited } else { Visibility::Hidden },
+                ..default()
+            },
+            RenderLayers::layer(**height_layer),
+            HeightProxy,
+            Name::new("Height proxy"),
+        )).id();
+
+        commands.entity(entity).add_child(proxy);
+    }
+}
+
+fn update_height_proxies(
+    elevated_q: Query<(&Elevation, &Children), Changed<Elevation>>,
+    mut proxy_q: Query<(&Handle<ColorMaterial>, &mut Visibility), With<HeightProxy>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (elevation, children) in elevated_q.iter() {
+        let mut proxies = proxy_q.iter_many_mut(children);
+
+        while let Some((material, mut visibility)) = proxies.fetch_next() {
+            // Parts on the ground have nothing to add to the cleared height texture
+            visibility.set_if_neq(if elevation.0 > 0.0 { Visibility::Inherited } else { Visibility::Hidden });
+
+            if let Some(material) = materials.get_mut(material) {
+                material.color = height_color(elevation.0);
+            }
+        }
     }
-}
\ No newline at end of file
+}
+
+// Elevation as a linear shade from black on the ground to white at `MAX_ELEVATION`
+fn height_color(elevation: f32) -> Color {
+    let height = (elevation / MAX_ELEVATION).clamp(0.0, 1.0);
+    Color::rgb_linear(height, height, height)
+}
+
+fn apply_shadow_settings(
+    settings: Res<ShadowSettings>,
+    shadow_material: Res<ShadowMaterial>,
+    mut custom_materials: ResMut<Assets<CustomMaterial>>,
+) {
+    if !settings.is_changed() { return; }
+
+    let Some(material) = custom_materials.get_mut(&shadow_material.0) else { return; };
+
+    material.shadow_offset = settings.offset();
+    material.shadow_blur = settings.blur;
+    material.shadow_height_scale = settings.height_scale;
+}
+
+// Sizes the render textures to as many whole pixels as fit in the primary window and centers
+// the quad showing them. Whatever is left over stays as bars of the main camera's clear color.
+fn fit_render_targets(
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    targets: Res<RenderTargets>,
+    mut view: ResMut<RenderTexView>,
+    mut dimensions: ResMut<ScreenDimensions>,
+    mut images: ResMut<Assets<Image>>,
+    mut custom_materials: ResMut<Assets<CustomMaterial>>,
+    mut quad_q: Query<&mut Transform>,
+) {
+    let Ok(window) = window_q.get_single() else { return; };
+
+    let scale = view.pixel_scale_factor.max(1);
+    let width = (window.width() as u32 / scale).max(1);
+    let height = (window.height() as u32 / scale).max(1);
+
+    if dimensions.width != width || dimensions.height != height {
+        dimensions.width = width;
+        dimensions.height = height;
+
+        let size = Extent3d { width, height, ..default() };
+        for handle in targets.images.iter() {
+            if let Some(image) = images.get_mut(handle) {
+                image.resize(size);
+            }
+        }
+
+        // The material's bind group still points at the old textures until the material changes
+        custom_materials.get_mut(&targets.material);
+    }
+
+    let quad_size = vec2(width as f32, height as f32) * scale as f32;
+    // Uneven bars would put the quad's edges, and so every texel, between two window pixels
+    let bars = (vec2(window.width(), window.height()) - quad_size) * 0.5;
+
+    // Not a change anyone has to react to, and marking it would run this again next frame
+    view.bypass_change_detection().origin = bars.floor();
+
+    if let Ok(mut transform) = quad_q.get_mut(targets.quad) {
+        // The world's Y axis points up, the window's points down
+        let offset = bars.fract() * vec2(-1.0, 1.0);
+
+        transform.translation = offset.extend(transform.translation.z);
+        transform.scale = quad_size.extend(1.0);
+    }
+}
@@ -0,0 +1,287 @@
+// Chain solvers for spines, tails and anything else made of evenly spaced points.
+// Kept free of ECS types so they can be tested without running the app.
+
+use std::f32::consts::PI;
+
+use bevy::math::*;
+
+// Pulls every point towards the one in front of it so that consecutive points end up
+// exactly `spacing` apart. The first point follows `anchor`.
+pub fn solve_follow(points: &mut [Vec2], anchor: Vec2, spacing: f32) {
+    follow(points, anchor, Vec2::NEG_Y, spacing, |_| None);
+}
+
+// Same as `solve_follow`, but no link may bend more than its entry in `max_bends` (radians)
+// away from the link in front of it. Links past the end of `max_bends` bend freely.
+// `anchor_dir` is the direction pointing from the anchor back down the chain, e.g. the head's backward vector.
+pub fn solve_follow_constrained(
+    points: &mut [Vec2],
+    anchor: Vec2,
+    anchor_dir: Vec2,
+    spacing: f32,
+    max_bends: &[f32],
+) {
+    follow(points, anchor, anchor_dir, spacing, |i| max_bends.get(i).copied());
+}
+
+// The loop behind both follow solvers. `max_bend` gives each link's bend limit, if it has one.
+fn follow(
+    points: &mut [Vec2],
+    anchor: Vec2,
+    anchor_dir: Vec2,
+    spacing: f32,
+    max_bend: impl Fn(usize) -> Option<f32>,
+) {
+    let mut target = anchor;
+    // Also used when a point sits exactly on its target and has no direction of its own
+    let mut prev_dir = anchor_dir.try_normalize().unwrap_or(Vec2::NEG_Y);
+
+    for (i, point) in points.iter_mut().enumerate() {
+        let mut dir = (*point - target).try_normalize().unwrap_or(prev_dir);
+
+        if let Some(max_bend) = max_bend(i) {
+            dir = clamp_bend(dir, prev_dir, max_bend);
+        }
+
+        *point = target + dir * spacing;
+
+        prev_dir = dir;
+        target = *point;
+    }
+}
+
+// Rotates `dir` back towards `prev_dir` until the angle between them is at most `max_bend`
+pub fn clamp_bend(dir: Vec2, prev_dir: Vec2, max_bend: f32) -> Vec2 {
+    let angle = prev_dir.angle_between(dir);
+
+    if angle.abs() <= max_bend { return dir; }
+
+    Vec2::from_angle(max_bend.copysign(angle)).rotate(prev_dir)
+}
+
+// FABRIK (forward and backward reaching IK). `points[0]` reaches for `target` while
+// `points[root]` stays fixed at `root_pos`, alternating passes from each end until the
+// first point is within `tolerance` of `target`. Points past `root` are left untouched.
+pub fn solve_fabrik(
+    points: &mut [Vec2],
+    target: Vec2,
+    root: usize,
+    root_pos: Vec2,
+    spacing: f32,
+    iterations: usize,
+    tolerance: f32,
+) {
+    if points.is_empty() { return; }
+
+    let root = root.min(points.len() - 1);
+    let chain = &mut points[..=root];
+
+    // Out of reach, so the best we can do is point the whole chain straight at the target
+    if root_pos.distance(target) >= spacing * root as f32 {
+        let dir = (target - root_pos).try_normalize().unwrap_or(Vec2::Y);
+
+        for (i, point) in chain.iter_mut().enumerate() {
+            *point = root_pos + dir * spacing * (root - i) as f32;
+        }
+        return;
+    }
+
+    for _ in 0..iterations {
+        chain[0] = target;
+        for i in 1..=root {
+            chain[i] = reach(chain[i - 1], chain[i], spacing);
+        }
+
+        chain[root] = root_pos;
+        for i in (0..root).rev() {
+            chain[i] = reach(chain[i + 1], chain[i], spacing);
+        }
+
+        if chain[0].distance(target) <= tolerance { break; }
+    }
+}
+
+// The point `spacing` away from `from` in the direction of `towards`
+fn reach(from: Vec2, towards: Vec2, spacing: f32) -> Vec2 {
+    from + (towards - from).try_normalize().unwrap_or(Vec2::NEG_Y) * spacing
+}
+
+// Rotation around Z that makes a part at `point` face `target`, with +Y as forward
+pub fn heading_angle(point: Vec2, target: Vec2) -> f32 {
+    let diff = target - point;
+    diff.y.atan2(diff.x) - PI * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_spacing(points: &[Vec2], anchor: Vec2, spacing: f32) {
+        let mut target = anchor;
+        for point in points {
+            assert!(point.is_finite(), "point {point} is not finite");
+            assert!(
+                (point.distance(target) - spacing).abs() < EPSILON,
+                "expected spacing {spacing}, got {}", point.distance(target)
+            );
+            target = *point;
+        }
+    }
+
+    #[test]
+    fn follow_preserves_spacing() {
+        let mut points: Vec<Vec2> = (1..=5).map(|i| vec2(0.0, -(i as f32) * 7.5)).collect();
+        let anchor = vec2(12.0, 4.0);
+
+        solve_follow(&mut points, anchor, 7.5);
+
+        assert_spacing(&points, anchor, 7.5);
+    }
+
+    #[test]
+    fn follow_keeps_settled_chain_in_place() {
+        let original: Vec<Vec2> = (1..=5).map(|i| vec2(0.0, -(i as f32) * 7.5)).collect();
+        let mut points = original.clone();
+
+        solve_follow(&mut points, Vec2::ZERO, 7.5);
+
+        for (point, original) in points.iter().zip(original.iter()) {
+            assert!(point.distance(*original) < EPSILON);
+        }
+    }
+
+    #[test]
+    fn follow_handles_zero_length_differences() {
+        // Every point stacked on the anchor, which used to normalize a zero vector into NaN
+        let mut points = vec![Vec2::ZERO; 4];
+
+        solve_follow(&mut points, Vec2::ZERO, 5.0);
+
+        assert_spacing(&points, Vec2::ZERO, 5.0);
+    }
+
+    #[test]
+    fn follow_reuses_previous_direction_for_coincident_points() {
+        let mut points = vec![vec2(10.0, 0.0), vec2(10.0, 0.0)];
+
+        solve_follow(&mut points, Vec2::ZERO, 5.0);
+
+        assert!(points[0].distance(vec2(5.0, 0.0)) < EPSILON);
+        assert!(points[1].distance(vec2(10.0, 0.0)) < EPSILON);
+    }
+
+    #[test]
+    fn follow_long_chain() {
+        let mut points: Vec<Vec2> = (0..1000)
+            .map(|i| vec2((i as f32 * 0.37).sin() * 50.0, -(i as f32) * 2.0))
+            .collect();
+        let anchor = vec2(-30.0, 80.0);
+
+        solve_follow(&mut points, anchor, 3.0);
+
+        assert_spacing(&points, anchor, 3.0);
+    }
+
+    #[test]
+    fn constrained_follow_respects_max_bend() {
+        // Chain folded back on itself into a hairpin behind the anchor
+        let mut points = vec![vec2(0.0, -5.0), vec2(3.0, -6.0), vec2(5.0, 0.0), vec2(3.0, 4.0)];
+        let max_bends = [0.3; 4];
+
+        solve_follow_constrained(&mut points, Vec2::ZERO, Vec2::NEG_Y, 5.0, &max_bends);
+
+        assert_spacing(&points, Vec2::ZERO, 5.0);
+
+        let mut target = Vec2::ZERO;
+        let mut prev_dir = Vec2::NEG_Y;
+        for point in points.iter() {
+            let dir = (*point - target).normalize();
+            assert!(prev_dir.angle_between(dir).abs() <= 0.3 + EPSILON);
+            prev_dir = dir;
+            target = *point;
+        }
+    }
+
+    #[test]
+    fn constrained_follow_matches_unconstrained_within_limits() {
+        let original: Vec<Vec2> = (1..=6)
+            .map(|i| vec2((i as f32 * 0.4).sin() * 2.0, -(i as f32) * 5.0))
+            .collect();
+        let anchor = vec2(1.0, 2.0);
+
+        let mut free = original.clone();
+        solve_follow(&mut free, anchor, 5.0);
+
+        let mut constrained = original.clone();
+        solve_follow_constrained(&mut constrained, anchor, Vec2::NEG_Y, 5.0, &[PI; 6]);
+
+        for (free, constrained) in free.iter().zip(constrained.iter()) {
+            assert!(free.distance(*constrained) < EPSILON);
+        }
+    }
+
+    #[test]
+    fn constrained_follow_keeps_spacing_past_missing_limits() {
+        let mut points: Vec<Vec2> = (1..=6).map(|i| vec2(i as f32 * 3.0, -(i as f32) * 5.0)).collect();
+        let anchor = vec2(0.0, 20.0);
+
+        solve_follow_constrained(&mut points, anchor, Vec2::NEG_Y, 5.0, &[0.3; 2]);
+
+        assert_spacing(&points, anchor, 5.0);
+    }
+
+    #[test]
+    fn clamp_bend_keeps_side() {
+        let clamped = clamp_bend(Vec2::X, Vec2::NEG_Y, 0.5);
+        assert!((Vec2::NEG_Y.angle_between(clamped) - 0.5).abs() < EPSILON);
+
+        let clamped = clamp_bend(Vec2::NEG_X, Vec2::NEG_Y, 0.5);
+        assert!((Vec2::NEG_Y.angle_between(clamped) + 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn fabrik_reaches_target_with_root_fixed() {
+        let mut points: Vec<Vec2> = (0..8).map(|i| vec2(0.0, -(i as f32) * 5.0)).collect();
+        let root_pos = points[7];
+        let target = vec2(10.0, -15.0);
+
+        solve_fabrik(&mut points, target, 7, root_pos, 5.0, 30, 0.01);
+
+        assert!(points[0].distance(target) <= 0.01);
+        assert!(points[7].distance(root_pos) < EPSILON);
+        for pair in points.windows(2) {
+            assert!((pair[0].distance(pair[1]) - 5.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn fabrik_stretches_towards_unreachable_target() {
+        let mut points: Vec<Vec2> = (0..4).map(|i| vec2(0.0, -(i as f32) * 5.0)).collect();
+        let root_pos = points[3];
+
+        solve_fabrik(&mut points, vec2(0.0, 100.0), 3, root_pos, 5.0, 10, 0.01);
+
+        for (i, point) in points.iter().enumerate() {
+            assert!(point.distance(root_pos + vec2(0.0, 5.0 * (3 - i) as f32)) < EPSILON);
+        }
+    }
+
+    #[test]
+    fn fabrik_leaves_points_past_root_alone() {
+        let mut points: Vec<Vec2> = (0..6).map(|i| vec2(0.0, -(i as f32) * 5.0)).collect();
+        let tail = points[4..].to_vec();
+        let root_pos = points[3];
+
+        solve_fabrik(&mut points, vec2(3.0, 1.0), 3, root_pos, 5.0, 10, 0.01);
+
+        assert_eq!(&points[4..], &tail[..]);
+    }
+
+    #[test]
+    fn heading_points_up_at_target() {
+        assert!(heading_angle(Vec2::ZERO, vec2(0.0, 1.0)).abs() < EPSILON);
+        assert!((heading_angle(Vec2::ZERO, vec2(-1.0, 0.0)) - PI * 0.5).abs() < EPSILON);
+    }
+}
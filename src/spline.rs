@@ -0,0 +1,102 @@
+// Curves through a sequence of points, used to smooth out the body outline.
+// Like `spine`, this stays free of ECS types.
+
+use bevy::math::*;
+
+// Uniform Catmull-Rom segment running from `p1` at `t = 0` to `p2` at `t = 1`
+pub fn catmull_rom(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, t: f32) -> Vec2 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+// How many points `sample_catmull_rom` produces for `len` control points
+pub fn sample_count(len: usize, subdivisions: usize) -> usize {
+    match len {
+        0 => 0,
+        len => (len - 1) * subdivisions.max(1) + 1,
+    }
+}
+
+// Appends a smooth curve through every point in `points` to `out`, with `subdivisions`
+// samples per segment. The curve passes through every control point and keeps going
+// straight at both ends.
+pub fn sample_catmull_rom(points: &[Vec2], subdivisions: usize, out: &mut Vec<Vec2>) {
+    let subdivisions = subdivisions.max(1);
+    let len = points.len();
+
+    if len < 2 {
+        out.extend_from_slice(points);
+        return;
+    }
+
+    // Mirror the neighbours of the end points so the first and last segments have something to bend towards
+    let point = |i: isize| -> Vec2 {
+        if i < 0 {
+            2.0 * points[0] - points[1]
+        } else if i as usize >= len {
+            2.0 * points[len - 1] - points[len - 2]
+        } else {
+            points[i as usize]
+        }
+    };
+
+    for segment in 0..len - 1 {
+        let i = segment as isize;
+        let (p0, p1, p2, p3) = (point(i - 1), point(i), point(i + 1), point(i + 2));
+
+        for step in 0..subdivisions {
+            out.push(catmull_rom(p0, p1, p2, p3, step as f32 / subdivisions as f32));
+        }
+    }
+
+    out.push(points[len - 1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    #[test]
+    fn catmull_rom_passes_through_control_points() {
+        let points = [vec2(0.0, 0.0), vec2(3.0, 1.0), vec2(5.0, -2.0), vec2(9.0, 0.0)];
+        let mut samples = Vec::new();
+
+        sample_catmull_rom(&points, 4, &mut samples);
+
+        assert_eq!(samples.len(), sample_count(points.len(), 4));
+        for (i, point) in points.iter().enumerate() {
+            assert!(samples[i * 4].distance(*point) < EPSILON);
+        }
+    }
+
+    #[test]
+    fn catmull_rom_keeps_straight_lines_straight() {
+        let points: Vec<Vec2> = (0..5).map(|i| vec2(0.0, -(i as f32) * 7.5)).collect();
+        let mut samples = Vec::new();
+
+        sample_catmull_rom(&points, 3, &mut samples);
+
+        for (i, sample) in samples.iter().enumerate() {
+            assert!(sample.distance(vec2(0.0, -(i as f32) * 2.5)) < EPSILON);
+        }
+    }
+
+    #[test]
+    fn catmull_rom_handles_short_input() {
+        let mut samples = Vec::new();
+
+        sample_catmull_rom(&[], 4, &mut samples);
+        assert!(samples.is_empty());
+
+        sample_catmull_rom(&[vec2(1.0, 2.0)], 4, &mut samples);
+        assert_eq!(samples, vec![vec2(1.0, 2.0)]);
+        assert_eq!(sample_count(1, 4), 1);
+    }
+}
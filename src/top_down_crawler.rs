@@ -4,26 +4,173 @@
 use std::f32::consts::PI;
 use rand::Rng;
 
-use bevy::{prelude::*, math::*, sprite::{MaterialMesh2dBundle, Mesh2dHandle}, render::{render_resource::PrimitiveTopology, mesh::Indices}};
-use crate::{cursor::*, builders::*};
+use bevy::{prelude::*, math::*, sprite::{Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle}, render::{render_resource::PrimitiveTopology, mesh::{Indices, VertexAttributeValues}}};
+use serde::Deserialize;
 
-const BODY_COLOR: Color = Color::rgb(0.3, 0.75, 0.35);
-const DARK_BODY_COLOR: Color = Color::rgb(0.1, 0.6, 0.3);
+use crate::{cursor::*, creature_definition::*, render_shadows::Elevation, body_animation::*, body_pattern::*, footprints::*, easing_functions::Easing, interpolation::*, spine, spline, ik};
 
-pub struct TopDownCrawlerPlugin;
+pub struct TopDownCrawlerPlugin {
+    // Simulation ticks per second, independent of the display frame rate
+    pub tick_rate: f64,
+}
 
 impl Plugin for TopDownCrawlerPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(Update, (control_vertebrae, control_feet, lerp_feet, update_mesh, control_eyes))
+            .add_plugins(Material2dPlugin::<BodyPatternMaterial>::default())
+            .init_asset::<CreatureDefinition>()
+            .init_asset_loader::<CreatureDefinitionLoader>()
+            .add_event::<FootLifted>()
+            .add_event::<FootPlanted>()
+            .insert_resource(Time::<Fixed>::from_hz(self.tick_rate))
+            .add_systems(FixedUpdate, (
+                restore_simulated_transforms,
+                (control_vertebrae, control_feet, swing_feet, spawn_footprints).chain(),
+                store_simulated_transforms,
+            ).chain())
+            .add_systems(Update, (
+                reload_creature_definitions,
+                apply_deferred,
+                spawn_creatures_from_definitions,
+                apply_deferred,
+                prune_spines,
+                generate_mesh,
+            ).chain().before(interpolate_transforms))
+            .add_systems(Update, (interpolate_transforms, (update_mesh, solve_legs, control_eyes)).chain())
+            .add_systems(Update, fade_footprints)
+            .add_systems(Update, animate_body_width.before(update_mesh))
             .add_systems(Startup, spawn_crawler)
-            .add_systems(PostStartup, generate_mesh)
         ;
     }
 }
 
+// Root entity of a creature, owning its parts so every system can work on one creature at a time
+#[derive(Component)]
+pub struct Creature {
+    pub head: Entity,
+    pub body_mesh: Option<Entity>,
+    pub body_shape: BodyShape,
+}
+
+// How the body mesh is laid out around the spine
+#[derive(Debug, Clone, Copy)]
+pub struct BodyShape {
+    // Outline samples between two vertebrae, at least 1
+    pub subdivisions: usize,
+    // Closes the front of the body around the head instead of leaving it to its own circle
+    pub head_cap: Option<HeadCap>,
+    // Rounds off the body behind the last vertebra
+    pub tail_cap: bool,
+    pub uv_mapping: UvMapping,
+    // Added to the body's z so overlapping creatures draw one over the other instead of z-fighting
+    pub depth: f32,
+}
+
+// How the body's u coordinate, running along the spine, is laid out. v always runs across the body.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+pub enum UvMapping {
+    // One unit per vertebra, counting up from the last one
+    #[default]
+    PerVertebra,
+    // 0 at the tip of the tail to 1 at the front of the body
+    Normalized,
+    // One unit every `length` world units, so a texture repeats at the same size on any creature
+    Tiled { length: f32 },
+}
+
+// Draws the body with a texture instead of a flat color. Lives on the root next to `Creature`.
 #[derive(Component)]
-pub struct Creature;
+pub struct BodySkin {
+    pub texture: Handle<Image>,
+    pub tint: Color,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HeadCap {
+    // Width and length of the head, which sits in the middle of the cap
+    pub size: Vec2,
+    // 1 is a plain half ellipse, larger values narrow it into a pointier snout
+    pub snout: f32,
+}
+
+// The explicit chain of a creature's vertebrae, which lives next to `Creature` on the root.
+// Query iteration order is not stable, so anything that walks the body must go through this.
+#[derive(Component, Default)]
+pub struct Spine {
+    // Ordered from the neck to the tip of the tail
+    pub vertebrae: Vec<Entity>,
+}
+
+// How a creature's spine reacts to its head being steered
+#[derive(Component, Deserialize, Debug, Clone, Copy, Default)]
+pub enum ChainSolver {
+    // Every vertebra is dragged along behind the one in front of it
+    #[default]
+    Follow,
+    // Forward and backward reaching IK, which can keep a vertebra pinned by a `SpineAnchor`
+    // in place while the head reaches for the cursor. Acts like `Follow` when nothing is pinned.
+    Fabrik {
+        iterations: usize,
+        tolerance: f32,
+    },
+}
+
+// Pins `vertebra`, one of the creature's `Spine`, to a world position,
+// e.g. a creature held by its tail or a tentacle rooted to the ground
+#[derive(Component)]
+pub struct SpineAnchor {
+    pub vertebra: Entity,
+    pub position: Vec2,
+}
+
+// How a creature coordinates stepping across all of its footed vertebrae.
+// Every foot gets a stepping order and feet with the same order step together. The groups take
+// turns from the lowest order up; the values only rank the groups and set no timing of their own.
+#[derive(Component, Deserialize, Debug, Clone, Default)]
+pub enum Gait {
+    // Diagonal pairs: front left with rear right, front right with rear left
+    #[default]
+    Trot,
+    // One foot at a time, rear to front on the left side and then on the right
+    Walk,
+    // Both feet on the same side together
+    Pace,
+    // Front pair and rear pair take turns
+    Bound,
+    // Explicit stepping orders, left then right for each footed vertebra from the neck back.
+    // Feet without an entry get 0.
+    Custom(Vec<f32>),
+}
+
+impl Gait {
+    // `pair` counts footed vertebrae from the neck back, out of `pairs`
+    pub fn step_order(&self, pair: usize, side: Side, pairs: usize) -> f32 {
+        let side_index = match side {
+            Side::Left => 0,
+            Side::Right => 1,
+        };
+
+        match self {
+            Gait::Trot => ((pair + side_index) % 2) as f32 * 0.5,
+            Gait::Walk => side_index as f32 * 0.5 + (pairs - 1 - pair) as f32 / (2 * pairs) as f32,
+            Gait::Pace => side_index as f32 * 0.5,
+            Gait::Bound => (pair % 2) as f32 * 0.5,
+            Gait::Custom(orders) => orders.get(pair * 2 + side_index).copied().unwrap_or(0.0),
+        }
+    }
+}
+
+// Which group of feet gets to step next
+#[derive(Component, Default)]
+pub struct GaitState {
+    pub next_group: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
 
 #[derive(Component)]
 pub struct Controllable {
@@ -36,25 +183,111 @@ pub struct Controllable {
 pub struct Vertebra {
     pub foot_offset: Vec2,
     pub step_length: f32,
+    // Seconds of the body's motion to place feet ahead by
+    pub step_lead: f32,
+    // How much of the body's turning to anticipate when placing feet
+    pub turn_lead: f32,
     pub width: f32,
+    // Animated on top of `width`, see `body_animation`
+    pub width_scale: f32,
+    // Multiplied with the body's color or skin around this vertebra
+    pub color: Color,
+    pub max_bend: f32,
 
     pub foot_l: Option<Entity>,
     pub foot_r: Option<Entity>,
+    pub leg_l: Option<Entity>,
+    pub leg_r: Option<Entity>,
 }
 
 #[derive(Component)]
 pub struct Head {
     pub z_index: f32,
     pub neck_width: f32,
+    pub color: Color,
 }
 
 #[derive(Component)]
 pub struct Foot {
-    pub foot_speed: f32,
     pub z_index: f32,
 
     pub target_pos: Vec2,
     pub grounded: bool,
+
+    // The current step, swinging from `step_start` to `target_pos` over `step_duration` seconds
+    pub step_start: Vec2,
+    pub step_time: f32,
+    pub step_duration: f32,
+    pub easing: Easing,
+
+    // How far off the ground the foot is, from 0 when planted to 1 at the top of a step
+    pub lift: f32,
+    // Sprite scale at the top of a step, making lifted feet read as closer to the camera
+    pub lift_scale: f32,
+    // Height at the top of a step, which pushes the foot's shadow away from it
+    pub lift_height: f32,
+}
+
+impl Foot {
+    pub fn lift_off(&mut self, target_pos: Vec2) {
+        // A planted foot always stands on the target of its last step
+        self.step_start = self.target_pos;
+        self.target_pos = target_pos;
+        self.step_time = 0.0;
+        self.grounded = false;
+    }
+}
+
+// Sent when a foot leaves the ground at `position` to start a step
+#[derive(Event, Debug, Clone, Copy)]
+pub struct FootLifted {
+    pub creature: Entity,
+    pub foot: Entity,
+    pub side: Side,
+    pub position: Vec2,
+}
+
+// Sent when a foot lands at `position` at the end of a step
+#[derive(Event, Debug, Clone, Copy)]
+pub struct FootPlanted {
+    pub creature: Entity,
+    pub foot: Entity,
+    pub side: Side,
+    pub position: Vec2,
+}
+
+impl Vertebra {
+    // How wide the body is drawn at this vertebra right now
+    pub fn current_width(&self) -> f32 {
+        self.width * self.width_scale
+    }
+
+    // Where the foot on `side` wants to stand, given the vertebra's current transform
+    pub fn foot_rest_position(&self, transform: &Transform, side: Side) -> Vec2 {
+        let lateral = match side {
+            Side::Left => transform.left(),
+            Side::Right => transform.right(),
+        };
+
+        (transform.translation 
+            + lateral * self.foot_offset.x 
+            + transform.up() * self.foot_offset.y).truncate()
+    }
+}
+
+// A limb connecting a vertebra's shoulder to one of its feet, solved with two-bone IK
+#[derive(Component)]
+pub struct Leg {
+    pub upper_length: f32,
+    pub lower_length: f32,
+    // Direction the knee bends towards in the vertebra's space, with +Y towards the head
+    pub knee_direction: Vec2,
+    pub width: f32,
+
+    // Solved joint positions in world space
+    pub shoulder: Vec2,
+    pub knee: Vec2,
+    pub ankle: Vec2,
 }
 
 #[derive(Component)]
@@ -65,189 +298,339 @@ pub struct BodyMesh;
 
 pub fn spawn_crawler(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
 ) {
-    let vertebra_spacing = 7.5;
-
-    spawn_head(
-        HeadParams {
-            size: vec2(10.0, 10.0),
-            position: vec2(0.0, vertebra_spacing),
-
-            turn_speed: 2.0,
-            z_index: 2.0,
-            neck_width: 10.0,
-
-            vertebra_dist: vertebra_spacing,
-            move_speed: 87.5,
-            head_color: BODY_COLOR,
-
-            eye_size: 3.75,
-            pupil_size: 2.0,
-        }, 
-        &mut commands, 
-        &mut meshes, 
-        &mut materials
-    );
-    
-    spawn_vertebra(
-        BodyPartParams {
-            size: 12.5,
-            position: vec2(0.0, 0.0),
-        },
-        &mut commands
-    );
-
-    spawn_vertebra_feet(
-        FootBodyPartParams {
-            size: 15.0,
-            position: vec2(0.0, -vertebra_spacing),
-            
-            foot_radius: 3.75,
-            foot_offset: vec2(vertebra_spacing * 2.0, vertebra_spacing * 2.5),
-            step_length: vertebra_spacing * 4.0,
-            foot_z_index: -1.0,
-            foot_speed: 18.0,
-            foot_color: DARK_BODY_COLOR,
-        },
-        &mut commands, 
-        &mut meshes, 
-        &mut materials,
-    );
-
-    let body_sizes: Vec<f32> = 
-        vec![12.5, 10.75, 10.0, 10.0, 10.75, 12.5];
-    
-    for (i, size) in body_sizes.iter().enumerate() {
-        spawn_vertebra(
-            BodyPartParams {
-                size: *size,
-                position: vec2(0.0, -((i as f32) + 2.0) * vertebra_spacing),
+    let lizard = asset_server.load("creatures/lizard.creature.ron");
+
+    for (i, x) in [-60.0, 60.0].into_iter().enumerate() {
+        commands.spawn((
+            CreatureSpawn {
+                definition: lizard.clone(),
+                position: vec2(x, 0.0),
+                rotation: Quat::IDENTITY,
+                depth: i as f32 * 0.1,
+                anchor: None,
             },
-            &mut commands
-        );
-    }
-
-    spawn_vertebra_feet(
-        FootBodyPartParams {
-            size: 15.0,
-            position: vec2(0.0, -8.0 * vertebra_spacing),
-            
-            foot_radius: 3.0,
-            foot_offset: vec2(vertebra_spacing * 1.75, vertebra_spacing * 2.5),
-            step_length: vertebra_spacing * 4.0,
-            foot_z_index: -1.0,
-            foot_speed: 18.0,
-            foot_color: DARK_BODY_COLOR,
-        },
-        &mut commands, 
-        &mut meshes, 
-        &mut materials,
-    );
-
-    let tail_sizes: Vec<f32> = 
-        vec![12.5, 10.0, 8.25, 6.25, 5.0, 3.75, 2.75, 1.75, 1.0, 0.5];
-    
-    for (i, size) in tail_sizes.iter().enumerate() {
-        spawn_vertebra(
-            BodyPartParams {
-                size: *size,
-                position: vec2(0.0, -((i as f32) + 9.0) * vertebra_spacing),
-            },
-            &mut commands
-        );
+            Name::new("Lizard"),
+        ));
     }
 }
 
 fn control_vertebrae(
-    mut controllable_q: Query<(&mut Transform, &Controllable)>,
+    creature_q: Query<(&Creature, &Spine, &ChainSolver, Option<&SpineAnchor>)>,
+    mut controllable_q: Query<(&mut Transform, &Controllable), Without<Vertebra>>,
     mut follower_vertebra_q: Query<
-        &mut Transform, 
-        (With<Vertebra>, Without<Controllable>)>,
+        (&mut Transform, &Vertebra), 
+        Without<Controllable>>,
     mouse_pos: Res<CursorWorldPos>,
     mouse_input: Res<Input<MouseButton>>,
     time: Res<Time>,
 ) {
     if !mouse_input.pressed(MouseButton::Left) { return; }
 
-    let (mut steer_transform, controllable) = controllable_q.get_single_mut()
-        .expect("At least one body part needs to have the Controllable component");
-
-    let target_diff = mouse_pos.as_ref().0.extend(0.0) - steer_transform.translation;
-    let target_angle = target_diff.y.atan2(target_diff.x);
-
-    if target_diff.length() < 4.0 { return; }
-
-    steer_transform.translation += target_diff.normalize() * controllable.move_speed * time.delta_seconds();
-
-    steer_transform.rotation = steer_transform.rotation.slerp(Quat::from_axis_angle(Vec3::Z, target_angle - PI * 0.5), time.delta_seconds() * controllable.turn_speed);
+    for (creature, spine, solver, spine_anchor) in creature_q.iter() {
+        let Ok((mut steer_transform, controllable)) = controllable_q.get_mut(creature.head) 
+            else { continue; };
 
-    let mut target_pos = steer_transform.translation;
+        let target_diff = mouse_pos.as_ref().0.extend(0.0) - steer_transform.translation;
+        let target_angle = target_diff.y.atan2(target_diff.x);
+
+        if target_diff.length() < 4.0 { continue; }
+
+        let head_target = (steer_transform.translation 
+            + target_diff.normalize() * controllable.move_speed * time.delta_seconds()).truncate();
+
+        steer_transform.rotation = steer_transform.rotation.slerp(Quat::from_axis_angle(Vec3::Z, target_angle - PI * 0.5), time.delta_seconds() * controllable.turn_speed);
+
+        let (mut points, max_bends): (Vec<Vec2>, Vec<f32>) = follower_vertebra_q.iter_many(&spine.vertebrae)
+            .map(|(transform, vertebra)| (transform.translation.truncate(), vertebra.max_bend))
+            .unzip();
+
+        // Nothing is pinned any more once the anchored vertebra has been removed
+        let anchor = spine_anchor.and_then(|anchor| spine.vertebrae.iter()
+            .position(|vertebra| *vertebra == anchor.vertebra)
+            .map(|index| (index, anchor.position)));
+
+        match (solver, anchor) {
+            (ChainSolver::Fabrik { iterations, tolerance }, Some((anchored, anchor_position))) 
+                if anchored < points.len() => 
+            {
+                // The head is the reaching end of the chain and the pinned vertebra its root
+                let root = anchored + 1;
+                let mut chain: Vec<Vec2> = std::iter::once(steer_transform.translation.truncate())
+                    .chain(points.iter().copied())
+                    .collect();
+
+                spine::solve_fabrik(
+                    &mut chain, 
+                    head_target, 
+                    root, 
+                    anchor_position, 
+                    controllable.vertebra_dist, 
+                    *iterations, 
+                    *tolerance,
+                );
+
+                // Anything behind the pinned vertebra just trails after it
+                let root_dir = (chain[root] - chain[root - 1]).try_normalize().unwrap_or(Vec2::NEG_Y);
+                let (pinned, trailing) = chain.split_at_mut(root + 1);
+
+                spine::solve_follow_constrained(
+                    trailing, 
+                    pinned[root], 
+                    root_dir, 
+                    controllable.vertebra_dist, 
+                    &max_bends[root..],
+                );
+
+                steer_transform.translation = chain[0].extend(steer_transform.translation.z);
+                points.copy_from_slice(&chain[1..]);
+            }
+            _ => {
+                steer_transform.translation = head_target.extend(steer_transform.translation.z);
+
+                spine::solve_follow_constrained(
+                    &mut points, 
+                    head_target, 
+                    steer_transform.down().truncate(), 
+                    controllable.vertebra_dist, 
+                    &max_bends,
+                );
+            }
+        }
 
-    for mut follower_transform in follower_vertebra_q.iter_mut() {
-        let diff = target_pos - follower_transform.translation;
-        let angle = diff.y.atan2(diff.x);
+        let mut target_pos = steer_transform.translation.truncate();
+        let mut followers = follower_vertebra_q.iter_many_mut(&spine.vertebrae);
 
-        follower_transform.translation += (diff.length() - controllable.vertebra_dist) * diff.normalize();
+        for point in points {
+            let Some((mut follower_transform, _)) = followers.fetch_next() else { break; };
 
-        follower_transform.rotation = Quat::from_axis_angle(Vec3::Z, angle - PI * 0.5);
+            follower_transform.translation = point.extend(follower_transform.translation.z);
+            follower_transform.rotation = Quat::from_axis_angle(Vec3::Z, spine::heading_angle(point, target_pos));
 
-        target_pos = follower_transform.translation;
+            target_pos = point;
+        }
     }
 }
 
+// A foot that may be asked to step this tick
+struct StepCandidate {
+    foot: Entity,
+    side: Side,
+    // Where the foot wants to stand right now
+    rest_pos: Vec2,
+    // Where it will be put down, ahead of `rest_pos` when the body is moving
+    step_pos: Vec2,
+    step_length: f32,
+    order: f32,
+}
+
 fn control_feet(
-    vertebra_q: Query<(&Transform, &Vertebra), Without<Foot>>,
+    mut creature_q: Query<(Entity, &Spine, &Gait, &mut GaitState)>,
+    vertebra_q: Query<(&Transform, &InterpolatedTransform, &Vertebra), Without<Foot>>,
     mut foot_q: Query<&mut Foot>,
-    mut gizmos: Gizmos,
+    mut lifted_events: EventWriter<FootLifted>,
+    time: Res<Time>,
 ) {
-    for (vertebra_transform, vertebra) in vertebra_q.iter() {
-        let (Some(foot_l_), Some(foot_r_)) = (vertebra.foot_l, vertebra.foot_r) else { continue; };
-
-        let Ok([mut foot_l, mut foot_r]) = foot_q.get_many_mut([foot_l_, foot_r_]) 
-            else { continue; };
-
-        let offset_pos_l = (vertebra_transform.translation
-            + vertebra_transform.left() * vertebra.foot_offset.x            
-            + vertebra_transform.up() * vertebra.foot_offset.y).truncate();
-
-        // gizmos.circle_2d(offset_pos_l, 5.0, Color::RED);
-        
-        if (offset_pos_l - foot_l.target_pos).length_squared() > vertebra.step_length.powf(2.0) && foot_r.grounded {
-            foot_l.grounded = false;
-            foot_l.target_pos = offset_pos_l;
+    for (creature, spine, gait, mut gait_state) in creature_q.iter_mut() {
+        let footed: Vec<(&Transform, &InterpolatedTransform, &Vertebra)> = vertebra_q.iter_many(&spine.vertebrae)
+            .filter(|(_, _, vertebra)| vertebra.foot_l.is_some() && vertebra.foot_r.is_some())
+            .collect();
+
+        let mut feet: Vec<StepCandidate> = Vec::with_capacity(footed.len() * 2);
+
+        for (pair, (vertebra_transform, interpolated, vertebra)) in footed.iter().enumerate() {
+            // `previous` still holds the last tick's state while the simulation runs
+            let (velocity, turn_rate) = vertebra_motion(
+                &interpolated.previous, 
+                vertebra_transform, 
+                time.delta_seconds(),
+            );
+
+            for (side, foot) in [(Side::Left, vertebra.foot_l), (Side::Right, vertebra.foot_r)] {
+                let Some(foot) = foot else { continue; };
+                let rest_pos = vertebra.foot_rest_position(vertebra_transform, side);
+
+                // Plant ahead of the shoulder along the way the body is moving and turning
+                let shoulder = vertebra_transform.translation.truncate();
+                let turned_rest = shoulder 
+                    + Vec2::from_angle(turn_rate * vertebra.step_lead * vertebra.turn_lead).rotate(rest_pos - shoulder);
+                let lead = (velocity * vertebra.step_lead).clamp_length_max(vertebra.step_length);
+
+                feet.push(StepCandidate {
+                    foot,
+                    side,
+                    rest_pos,
+                    step_pos: turned_rest + lead,
+                    step_length: vertebra.step_length,
+                    order: gait.step_order(pair, side, footed.len()),
+                });
+            }
         }
 
-        // gizmos.circle_2d(foot_l.target_pos, 5.0, Color::GREEN);
-        
-        let offset_pos_r = (vertebra_transform.translation
-            + vertebra_transform.right() * vertebra.foot_offset.x
-            + vertebra_transform.up() * vertebra.foot_offset.y).truncate();
+        // Feet sharing an order step together, and the groups take turns from the lowest order up
+        let mut groups: Vec<f32> = feet.iter().map(|candidate| candidate.order).collect();
+        groups.sort_by(|a, b| a.total_cmp(b));
+        groups.dedup_by(|a, b| (*a - *b).abs() < 1e-3);
+
+        let group_of = |order: f32| groups.iter()
+            .position(|group| (group - order).abs() < 1e-3)
+            .unwrap_or(0);
+
+        let needs_step = |candidate: &StepCandidate, threshold: f32| {
+            foot_q.get(candidate.foot).is_ok_and(|foot| 
+                (candidate.rest_pos - foot.target_pos).length_squared() 
+                    > (candidate.step_length * threshold).powf(2.0))
+        };
+
+        // Only the group already in the air may keep stepping until all of it has landed
+        let airborne_group = feet.iter()
+            .find(|candidate| foot_q.get(candidate.foot).is_ok_and(|foot| !foot.grounded))
+            .map(|candidate| group_of(candidate.order));
+
+        let (active_group, threshold) = match airborne_group {
+            Some(group) => (group, 1.0),
+            None => {
+                let next_group = (0..groups.len())
+                    .map(|offset| (gait_state.next_group + offset) % groups.len())
+                    .find(|group| feet.iter()
+                        .any(|candidate| group_of(candidate.order) == *group && needs_step(candidate, 1.0)));
+
+                let Some(group) = next_group else { continue; };
+                gait_state.next_group = (group + 1) % groups.len();
+
+                // Once a group has to move, its other feet come along if they are halfway there
+                (group, 0.5)
+            }
+        };
+
+        let stepping: Vec<&StepCandidate> = feet.iter()
+            .filter(|candidate| group_of(candidate.order) == active_group)
+            .filter(|candidate| foot_q.get(candidate.foot).is_ok_and(|foot| foot.grounded))
+            .filter(|candidate| needs_step(candidate, threshold))
+            .collect();
+
+        for candidate in stepping {
+            let Ok(mut foot) = foot_q.get_mut(candidate.foot) else { continue; };
+
+            lifted_events.send(FootLifted {
+                creature,
+                foot: candidate.foot,
+                side: candidate.side,
+                position: foot.target_pos,
+            });
+
+            foot.lift_off(candidate.step_pos);
+        }
+    }
+}
 
-        // gizmos.circle_2d(offset_pos_r, 5.0, Color::RED);
+// Velocity and signed turn rate (radians per second) of a part between two ticks
+fn vertebra_motion(previous: &Transform, current: &Transform, dt: f32) -> (Vec2, f32) {
+    if dt <= 0.0 { return (Vec2::ZERO, 0.0); }
 
-        if (offset_pos_r - foot_r.target_pos).length_squared() > vertebra.step_length.powf(2.0) && foot_l.grounded {
-            foot_r.grounded = false;
-            foot_r.target_pos = offset_pos_r;
-        }
+    let velocity = (current.translation - previous.translation).truncate() / dt;
+    let turn = previous.up().truncate().angle_between(current.up().truncate());
 
-        // gizmos.circle_2d(foot_r.target_pos, 5.0, Color::GREEN);
-    }
+    (velocity, if turn.is_finite() { turn / dt } else { 0.0 })
 }
 
-fn lerp_feet(
-    mut foot_q: Query<(&mut Transform, &mut Foot)>,
+// Walks the feet through each creature's spine so planting can be reported per creature and side
+fn swing_feet(
+    creature_q: Query<(Entity, &Spine)>,
+    vertebra_q: Query<&Vertebra>,
+    mut foot_q: Query<(&mut Transform, &mut Foot, &mut Elevation)>,
+    mut planted_events: EventWriter<FootPlanted>,
     time: Res<Time>,
 ) {
-    for (mut transform, mut foot) in foot_q.iter_mut() {
-        transform.translation = transform.translation.lerp(
-            foot.target_pos.extend(foot.z_index), 
-            time.delta_seconds() * foot.foot_speed
-        );
+    for (creature, spine) in creature_q.iter() {
+        for vertebra in vertebra_q.iter_many(&spine.vertebrae) {
+            for (side, foot_entity) in [(Side::Left, vertebra.foot_l), (Side::Right, vertebra.foot_r)] {
+                let Some(foot_entity) = foot_entity else { continue; };
+                let Ok((mut transform, mut foot, mut elevation)) = foot_q.get_mut(foot_entity) else { continue; };
+
+                if foot.grounded { continue; }
+
+                foot.step_time += time.delta_seconds();
+
+                let t = if foot.step_duration > 0.0 {
+                    (foot.step_time / foot.step_duration).min(1.0)
+                } else {
+                    1.0
+                };
+
+                let position = foot.step_start.lerp(foot.target_pos, foot.easing.apply(t));
+                foot.lift = (t * PI).sin();
+
+                // Plant the foot exactly on its target once the swing is over
+                if t >= 1.0 {
+                    foot.grounded = true;
+                    foot.lift = 0.0;
+
+                    planted_events.send(FootPlanted {
+                        creature,
+                        foot: foot_entity,
+                        side,
+                        position,
+                    });
+                }
+
+                transform.translation = position.extend(foot.z_index);
+                let scale = 1.0 + (foot.lift_scale - 1.0) * foot.lift;
+                transform.scale = vec3(scale, scale, 1.0);
+                elevation.0 = foot.lift_height * foot.lift;
+            }
+        }
+    }
+}
 
-        if (transform.translation.truncate() - foot.target_pos).length_squared() < 50.0 {
-            foot.grounded = true;
+// Bends every leg between its shoulder and foot, leaving legs whose body and foot stayed put alone
+fn solve_legs(
+    spine_q: Query<&Spine>,
+    vertebra_q: Query<(Ref<Transform>, Ref<Vertebra>)>,
+    foot_q: Query<Ref<Transform>, With<Foot>>,
+    mut leg_q: Query<(&mut Leg, &Mesh2dHandle)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (vertebra_transform, vertebra) in spine_q.iter()
+        .flat_map(|spine| vertebra_q.iter_many(&spine.vertebrae)) 
+    {
+        let sides = [
+            (vertebra.foot_l, vertebra.leg_l, Side::Left),
+            (vertebra.foot_r, vertebra.leg_r, Side::Right),
+        ];
+
+        for (foot, leg, side) in sides {
+            let (Some(foot), Some(leg)) = (foot, leg) else { continue; };
+            let Ok(foot_transform) = foot_q.get(foot) else { continue; };
+            let Ok((mut leg, mesh_handle)) = leg_q.get_mut(leg) else { continue; };
+
+            let moved = vertebra_transform.is_changed() || vertebra.is_changed() || foot_transform.is_changed();
+            if !moved && !leg.is_added() { continue; }
+
+            // The shoulder sits on the edge of the body, facing the foot's resting spot
+            let rest_dir = (vertebra.foot_rest_position(&vertebra_transform, side) 
+                - vertebra_transform.translation.truncate())
+                .normalize_or_zero();
+            let shoulder = vertebra_transform.translation.truncate() + rest_dir * vertebra.current_width() * 0.5;
+            let pole = (vertebra_transform.rotation * leg.knee_direction.extend(0.0)).truncate();
+
+            let (knee, ankle) = ik::solve_two_bone(
+                shoulder, 
+                foot_transform.translation.truncate(), 
+                leg.upper_length, 
+                leg.lower_length, 
+                pole,
+            );
+
+            leg.shoulder = shoulder;
+            leg.knee = knee;
+            leg.ankle = ankle;
+
+            let Some(mesh) = meshes.get_mut(mesh_handle.0.id()) else { continue; };
+            if let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION) {
+                write_leg_positions(&leg, positions);
+            }
         }
     }
 }
@@ -258,78 +641,345 @@ fn control_eyes(
     mut commands: Commands,
     mouse_pos: Res<CursorWorldPos>,
 ) {
-    let Ok((children, head_transform)) = head_q.get_single() else { return; };
+    for (children, head_transform) in head_q.iter() {
+        for child in children.iter() {
+            let Ok((mut transform, glob_transform)) = eye_q.get_mut(*child) else { continue; };
+
+            let dir = mouse_pos.as_ref().0 - glob_transform.translation().truncate();
+            let angle = dir.y.atan2(dir.x);
+        
+            transform.rotation = Quat::from_euler(EulerRot::XYZ, 0.0, 0.0, angle - head_transform.rotation.to_euler(EulerRot::XYZ).2 - PI * 0.5);
+        }
+    }
+}
 
-    for child in children.iter() {
-        let Ok((mut transform, glob_transform)) = eye_q.get_mut(*child) else { continue; };
+// Drops vertebrae that have been despawned so the chain stays connected
+fn prune_spines(
+    mut spine_q: Query<&mut Spine>,
+    vertebra_q: Query<(), With<Vertebra>>,
+) {
+    for mut spine in spine_q.iter_mut() {
+        // Only take the spine mutably when something is missing, so its change detection stays meaningful
+        if spine.vertebrae.iter().all(|vertebra| vertebra_q.contains(*vertebra)) { continue; }
 
-        let dir = mouse_pos.as_ref().0 - glob_transform.translation().truncate();
-        let angle = dir.y.atan2(dir.x);
-    
-        transform.rotation = Quat::from_euler(EulerRot::XYZ, 0.0, 0.0, angle - head_transform.rotation.to_euler(EulerRot::XYZ).2 - PI * 0.5);
+        spine.vertebrae.retain(|vertebra| vertebra_q.contains(*vertebra));
     }
 }
 
+// Above the feet and head, below the eyes
+const BODY_Z_INDEX: f32 = 10.0;
+
+// Whatever a creature's body is drawn with besides its plain color
+type BodyLook<'a> = (Option<&'a BodySkin>, Option<&'a BodyPatternSkin>);
+
+// Builds a body mesh for every creature whose spine is new or has changed,
+// whether from startup, a respawn or a vertebra being removed
 fn generate_mesh(
+    mut creature_q: Query<(&mut Creature, &Spine, BodyLook), Changed<Spine>>,
     vertebra_q: Query<(&Transform, &Vertebra)>,
     head_q: Query<(&Transform, &Head)>,
-    // mesh_q: Query<&Mesh2dHandle, With<BodyMesh>>,
+    mut builder: Local<BodyMeshBuilder>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    let mut positions: Vec<[f32; 3]> = Vec::new();
-    let mut uvs: Vec<[f32; 2]> = Vec::new();
-    let mut normals: Vec<[f32; 3]> = Vec::new();
-    let mut indices: Vec<u32> = Vec::new();
-
-    let (mut next_transform, head) = head_q.single();
-    let mut next_width = head.neck_width;
-    let body_len = vertebra_q.iter().len();
-
-    for (i, (transform, vertebra)) in vertebra_q.iter().enumerate() {
-        let lower_l = transform.translation + transform.left() * vertebra.width * 0.5;
-        let lower_r = transform.translation - transform.left() * vertebra.width * 0.5;
-
-        let upper_l = next_transform.translation + next_transform.left() * next_width * 0.5;
-        let upper_r = next_transform.translation - next_transform.left() * next_width * 0.5;
-
-        positions.append(&mut vec![
-            lower_l.to_array(), 
-            lower_r.to_array(), 
-            upper_l.to_array(), 
-            upper_r.to_array()
-        ]);
-
-        uvs.append(&mut vec![
-            [body_len as f32 - i as f32 - 1.0, 0.0], 
-            [body_len as f32 - i as f32 - 1.0, 1.0], 
-            [body_len as f32 - i as f32, 0.0], 
-            [body_len as f32 - i as f32, 1.0], 
-        ]);
-
-        normals.append(&mut vec![
-            [0.0, 0.0, 1.0],
-            [0.0, 0.0, 1.0],
-            [0.0, 0.0, 1.0],
-            [0.0, 0.0, 1.0],
-        ]);
-
-        indices.append(&mut vec![
-            0 + (4 * i) as u32, 
-            1 + (4 * i) as u32, 
-            3 + (4 * i) as u32,
-
-            3 + (4 * i) as u32, 
-            2 + (4 * i) as u32, 
-            0 + (4 * i) as u32,
-        ]);
-
-        next_transform = transform;
-        next_width = vertebra.width;
+    for (mut creature, spine, (skin, pattern)) in creature_q.iter_mut() {
+        if let Some(old_body_mesh) = creature.body_mesh.take() {
+            commands.entity(old_body_mesh).despawn_recursive();
+        }
+
+        let Ok((head_transform, head)) = head_q.get(creature.head) else { continue; };
+
+        builder.trace(
+            head_transform, 
+            head, 
+            vertebra_q.iter_many(&spine.vertebrae), 
+            &creature.body_shape,
+        );
+
+        let mesh = meshes.add(builder.build_mesh(head_transform, &creature.body_shape)).into();
+        let transform = Transform::from_translation(vec3(0.0, 0.0, BODY_Z_INDEX + creature.body_shape.depth));
+
+        let mut body_mesh = match pattern {
+            Some(pattern) => commands.spawn(MaterialMesh2dBundle {
+                mesh,
+                material: pattern.0.clone(),
+                transform,
+                ..default()
+            }),
+            None => commands.spawn(MaterialMesh2dBundle {
+                mesh,
+                material: materials.add(match skin {
+                    Some(skin) => ColorMaterial {
+                        color: skin.tint,
+                        texture: Some(skin.texture.clone()),
+                    },
+                    None => ColorMaterial::from(head.color),
+                }),
+                transform,
+                ..default()
+            }),
+        };
+
+        let body_mesh = body_mesh.insert(BodyMesh).id();
+
+        creature.body_mesh = Some(body_mesh);
+    }
+}
+
+// Traces body outlines into buffers that are kept around between creatures and frames,
+// so reshaping a body every frame does not allocate
+#[derive(Default)]
+pub struct BodyMeshBuilder {
+    // Left and right edge and the color at the neck and at every vertebra
+    edges_l: Vec<Vec2>,
+    edges_r: Vec<Vec2>,
+    joint_colors: Vec<Vec4>,
+
+    // The smoothed and capped outline, one row per entry, from the snout or neck back
+    outline_l: Vec<Vec2>,
+    outline_r: Vec<Vec2>,
+    outline_colors: Vec<[f32; 4]>,
+}
+
+impl BodyMeshBuilder {
+    // Smooths a spline through the edges of the neck and of every vertebra and closes it off
+    // with whichever caps `shape` asks for
+    pub fn trace<'a>(
+        &mut self,
+        head_transform: &'a Transform,
+        head: &Head,
+        vertebrae: impl IntoIterator<Item = (&'a Transform, &'a Vertebra)>,
+        shape: &BodyShape,
+    ) {
+        self.edges_l.clear();
+        self.edges_r.clear();
+        self.joint_colors.clear();
+        self.outline_l.clear();
+        self.outline_r.clear();
+        self.outline_colors.clear();
+
+        // A capped body is as wide as the head where the two meet
+        let neck_width = shape.head_cap.map_or(head.neck_width, |cap| cap.size.x);
+        let neck = (head_transform, neck_width, Color::WHITE);
+        let mut last_joint = neck;
+
+        let joints = vertebrae.into_iter()
+            .map(|(transform, vertebra)| (transform, vertebra.current_width(), vertebra.color));
+
+        for (transform, width, color) in std::iter::once(neck).chain(joints) {
+            let half_width = transform.left().truncate() * width * 0.5;
+            let center = transform.translation.truncate();
+
+            self.edges_l.push(center + half_width);
+            self.edges_r.push(center - half_width);
+            self.joint_colors.push(Vec4::from(color.as_linear_rgba_f32()));
+            last_joint = (transform, width, color);
+        }
+
+        if let Some(cap) = shape.head_cap {
+            let center = head_transform.translation.truncate();
+            let forward = head_transform.up().truncate();
+            let left = head_transform.left().truncate();
+            let rows = cap_rows(shape);
+
+            // Half an ellipse from the tip of the snout back to the widest point of the head
+            for row in 0..rows {
+                let along = 1.0 - row as f32 / rows as f32;
+                let half_width = cap.size.x * 0.5 * (1.0 - along * along).sqrt().powf(cap.snout);
+                let row_center = center + forward * cap.size.y * 0.5 * along;
+
+                self.outline_l.push(row_center + left * half_width);
+                self.outline_r.push(row_center - left * half_width);
+                self.outline_colors.push(self.joint_colors[0].to_array());
+            }
+        }
+
+        spline::sample_catmull_rom(&self.edges_l, shape.subdivisions, &mut self.outline_l);
+        spline::sample_catmull_rom(&self.edges_r, shape.subdivisions, &mut self.outline_r);
+
+        // Colors blend straight from one joint to the next
+        let subdivisions = shape.subdivisions.max(1);
+        for row in 0..spline::sample_count(self.joint_colors.len(), subdivisions) {
+            let (joint, step) = (row / subdivisions, row % subdivisions);
+            let next = (joint + 1).min(self.joint_colors.len() - 1);
+            let color = self.joint_colors[joint].lerp(self.joint_colors[next], step as f32 / subdivisions as f32);
+            self.outline_colors.push(color.to_array());
+        }
+
+        if shape.tail_cap && self.edges_l.len() > 1 {
+            let (transform, width, color) = last_joint;
+            let center = transform.translation.truncate();
+            let back = transform.down().truncate();
+            let left = transform.left().truncate();
+            let radius = width * 0.5;
+            let rows = cap_rows(shape);
+
+            // Quarter circle on each side, closing in on a point behind the last vertebra
+            for row in 1..=rows {
+                let angle = row as f32 / rows as f32 * PI * 0.5;
+                let row_center = center + back * radius * angle.sin();
+
+                self.outline_l.push(row_center + left * radius * angle.cos());
+                self.outline_r.push(row_center - left * radius * angle.cos());
+                self.outline_colors.push(color.as_linear_rgba_f32());
+            }
+        }
+    }
+
+    // A fresh mesh for the last traced outline
+    pub fn build_mesh(&self, head_transform: &Transform, shape: &BodyShape) -> Mesh {
+        let rows = self.outline_l.len();
+        let mut positions = Vec::with_capacity(rows * 2);
+        let mut colors = Vec::with_capacity(rows * 2);
+        self.write_positions(&mut positions);
+        self.write_colors(&mut colors);
+
+        // Runs from the neck at the number of vertebrae down to the last vertebra at 0, one unit
+        // per vertebra. The caps carry on past both ends in the same units.
+        let joints = self.edges_l.len();
+        let joint_center = |i: usize| (self.edges_l[i] + self.edges_r[i]) * 0.5;
+
+        let spacing = if joints > 1 { joint_center(0).distance(joint_center(1)) } else { 0.0 };
+        let spacing = if spacing > 0.0 { spacing } else { 1.0 };
+
+        let head_rows = shape.head_cap.map_or(0, |_| cap_rows(shape));
+        let tail_row = head_rows + spline::sample_count(joints, shape.subdivisions);
+        let neck = head_transform.translation.truncate();
+        let last_vertebra = joint_center(joints.saturating_sub(1));
+        let neck_u = joints.saturating_sub(1) as f32;
+
+        let us: Vec<f32> = self.outline_l.iter().zip(self.outline_r.iter())
+            .enumerate()
+            .map(|(row, (l, r))| {
+                let center = (*l + *r) * 0.5;
+                if row < head_rows {
+                    neck_u + center.distance(neck) / spacing
+                } else if row >= tail_row {
+                    -center.distance(last_vertebra) / spacing
+                } else {
+                    neck_u - (row - head_rows) as f32 / shape.subdivisions.max(1) as f32
+                }
+            })
+            .collect();
+
+        // Rows run from the front of the body back, so the first and last hold the extremes
+        let (u_front, u_back) = (us.first().copied().unwrap_or(0.0), us.last().copied().unwrap_or(0.0));
+
+        let uvs: Vec<[f32; 2]> = us.iter()
+            .flat_map(|u| {
+                let u = match shape.uv_mapping {
+                    UvMapping::PerVertebra => *u,
+                    UvMapping::Normalized if u_front > u_back => (u - u_back) / (u_front - u_back),
+                    UvMapping::Normalized => 0.0,
+                    UvMapping::Tiled { length } => u * spacing / length.max(f32::EPSILON),
+                };
+                [[u, 0.0], [u, 1.0]]
+            })
+            .collect();
+
+        // One continuous strip, two triangles between every row and the one behind it
+        let indices: Vec<u32> = (0..rows.saturating_sub(1) as u32)
+            .flat_map(|row| {
+                let (front, back) = (row * 2, row * 2 + 2);
+                [
+                    back, back + 1, front + 1,
+                    front + 1, front, back,
+                ]
+            })
+            .collect();
+
+        Mesh::new(PrimitiveTopology::TriangleList)
+            .with_inserted_attribute(
+                Mesh::ATTRIBUTE_POSITION, 
+                positions,
+            )
+
+            .with_inserted_attribute(
+                Mesh::ATTRIBUTE_UV_0, 
+                uvs,
+            )
+
+            .with_inserted_attribute(
+                Mesh::ATTRIBUTE_NORMAL, 
+                vec![[0.0, 0.0, 1.0]; rows * 2],
+            )
+
+            .with_inserted_attribute(
+                Mesh::ATTRIBUTE_COLOR, 
+                colors,
+            )
+
+            .with_indices(Some(Indices::U32(indices)))
+    }
+
+    // Overwrites the positions and colors of a mesh made by `build_mesh` with the last traced
+    // outline, reusing the mesh's own buffers
+    pub fn update_mesh(&self, mesh: &mut Mesh) {
+        if let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION) {
+            self.write_positions(positions);
+        }
+        if let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute_mut(Mesh::ATTRIBUTE_COLOR) {
+            self.write_colors(colors);
+        }
+    }
+
+    // Interleaved left then right edge of every row
+    fn write_positions(&self, positions: &mut Vec<[f32; 3]>) {
+        positions.clear();
+        positions.extend(self.outline_l.iter().zip(self.outline_r.iter())
+            .flat_map(|(l, r)| [l.extend(0.0).to_array(), r.extend(0.0).to_array()]));
+    }
+
+    // Both vertices of a row share its color
+    fn write_colors(&self, colors: &mut Vec<[f32; 4]>) {
+        colors.clear();
+        colors.extend(self.outline_colors.iter().flat_map(|color| [*color, *color]));
+    }
+}
+
+// Rows in each cap, not counting the row it shares with the rest of the body
+fn cap_rows(shape: &BodyShape) -> usize {
+    shape.subdivisions.max(1) * 2
+}
+
+// Reshapes the body mesh of every creature that moved or changed shape since the last frame
+fn update_mesh(
+    creature_q: Query<(&Creature, &Spine)>,
+    vertebra_q: Query<(Ref<Transform>, Ref<Vertebra>)>,
+    head_q: Query<(Ref<Transform>, &Head)>,
+    mesh_q: Query<&Mesh2dHandle, With<BodyMesh>>,
+    mut builder: Local<BodyMeshBuilder>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (creature, spine) in creature_q.iter() {
+        let Some(Ok(mesh_handle)) = creature.body_mesh.map(|body_mesh| mesh_q.get(body_mesh)) 
+            else { continue; };
+        let Ok((head_transform, head)) = head_q.get(creature.head) else { continue; };
+
+        let moved = head_transform.is_changed() 
+            || vertebra_q.iter_many(&spine.vertebrae)
+                .any(|(transform, vertebra)| transform.is_changed() || vertebra.is_changed());
+        if !moved { continue; }
+
+        let Some(mesh) = meshes.get_mut(mesh_handle.0.id()) else { continue; };
+
+        builder.trace(
+            &head_transform, 
+            head, 
+            vertebra_q.iter_many(&spine.vertebrae)
+                .map(|(transform, vertebra)| (transform.into_inner(), vertebra.into_inner())), 
+            &creature.body_shape,
+        );
+        builder.update_mesh(mesh);
     }
+}
+
+pub fn build_leg_mesh(leg: &Leg) -> Mesh {
+    let mut positions = Vec::with_capacity(6);
+    write_leg_positions(leg, &mut positions);
 
-    let mesh = Mesh::new(PrimitiveTopology::TriangleList)
+    Mesh::new(PrimitiveTopology::TriangleList)
         .with_inserted_attribute(
             Mesh::ATTRIBUTE_POSITION, 
             positions,
@@ -337,59 +987,93 @@ fn generate_mesh(
 
         .with_inserted_attribute(
             Mesh::ATTRIBUTE_UV_0, 
-            uvs,
+            vec![[0.0, 0.0], [0.0, 1.0], [0.5, 0.0], [0.5, 1.0], [1.0, 0.0], [1.0, 1.0]],
         )
 
         .with_inserted_attribute(
             Mesh::ATTRIBUTE_NORMAL, 
-            normals,
+            vec![[0.0, 0.0, 1.0]; 6],
         )
 
-        .with_indices(Some(Indices::U32(indices))
-    );
+        .with_indices(Some(Indices::U32(vec![
+            0, 1, 3,
+            3, 2, 0,
 
-    commands.spawn((
-        MaterialMesh2dBundle {
-            mesh: meshes.add(mesh).into(),
-            material: materials.add(ColorMaterial::from(BODY_COLOR)),
-            transform: Transform::from_translation(vec3(0.0, 0.0, 10.0)),
-            ..default()
-        },
-        BodyMesh,
-    ));
+            2, 3, 5,
+            5, 4, 2,
+        ])))
 }
 
-fn update_mesh(
-    vertebra_q: Query<(&Transform, &Vertebra)>,
-    head_q: Query<(&Transform, &Head)>,
-    mesh_q: Query<&Mesh2dHandle, With<BodyMesh>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-) {
-    let mut positions: Vec<[f32; 3]> = Vec::new();
+// Left and right edge of the leg at the shoulder, knee and ankle, tapering towards the foot
+fn write_leg_positions(leg: &Leg, positions: &mut Vec<[f32; 3]>) {
+    let upper_dir = (leg.knee - leg.shoulder).normalize_or_zero();
+    let lower_dir = (leg.ankle - leg.knee).normalize_or_zero();
+    let knee_dir = (upper_dir + lower_dir).normalize_or_zero();
+
+    positions.clear();
+    positions.extend([(leg.shoulder, upper_dir, 1.0), (leg.knee, knee_dir, 0.75), (leg.ankle, lower_dir, 0.5)]
+        .into_iter()
+        .flat_map(|(joint, dir, taper)| {
+            let half_width = dir.perp() * leg.width * taper * 0.5;
+            [
+                (joint + half_width).extend(0.0).to_array(),
+                (joint - half_width).extend(0.0).to_array(),
+            ]
+        }));
+}
 
-    let (mut next_transform, head) = head_q.single();
-    let mut next_width = head.neck_width;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    for (transform, vertebra) in vertebra_q.iter() {
-        let lower_l = transform.translation + transform.left() * vertebra.width * 0.5;
-        let lower_r = transform.translation - transform.left() * vertebra.width * 0.5;
+    const EPSILON: f32 = 1e-4;
 
-        let upper_l = next_transform.translation + next_transform.left() * next_width * 0.5;
-        let upper_r = next_transform.translation - next_transform.left() * next_width * 0.5;
+    // (left, right) stepping order of every pair from the neck back
+    fn orders(gait: &Gait, pairs: usize) -> Vec<(f32, f32)> {
+        (0..pairs)
+            .map(|pair| (gait.step_order(pair, Side::Left, pairs), gait.step_order(pair, Side::Right, pairs)))
+            .collect()
+    }
 
-        positions.append(&mut vec![
-            lower_l.to_array(), 
-            lower_r.to_array(), 
-            upper_l.to_array(), 
-            upper_r.to_array()
-        ]);
+    fn assert_orders(gait: Gait, pairs: usize, expected: &[(f32, f32)]) {
+        for (pair, (order, expected)) in orders(&gait, pairs).iter().zip(expected.iter()).enumerate() {
+            assert!(
+                (order.0 - expected.0).abs() < EPSILON && (order.1 - expected.1).abs() < EPSILON,
+                "{gait:?} pair {pair} of {pairs}: expected {expected:?}, got {order:?}"
+            );
+        }
+    }
 
-        next_transform = transform;
-        next_width = vertebra.width;
+    #[test]
+    fn trot_moves_diagonal_feet_together() {
+        assert_orders(Gait::Trot, 2, &[(0.0, 0.5), (0.5, 0.0)]);
+        assert_orders(Gait::Trot, 3, &[(0.0, 0.5), (0.5, 0.0), (0.0, 0.5)]);
+        assert_orders(Gait::Trot, 1, &[(0.0, 0.5)]);
     }
 
-    for mesh_handle in mesh_q.iter() {
-        let Some(mesh) = meshes.get_mut(mesh_handle.0.id()) else { continue; };
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions.clone());
+    #[test]
+    fn walk_steps_rear_to_front_one_side_at_a_time() {
+        assert_orders(Gait::Walk, 2, &[(0.25, 0.75), (0.0, 0.5)]);
+        assert_orders(Gait::Walk, 1, &[(0.0, 0.5)]);
+    }
+
+    #[test]
+    fn pace_moves_each_side_together() {
+        assert_orders(Gait::Pace, 2, &[(0.0, 0.5), (0.0, 0.5)]);
+        assert_orders(Gait::Pace, 1, &[(0.0, 0.5)]);
+    }
+
+    #[test]
+    fn bound_alternates_front_and_rear_pairs() {
+        assert_orders(Gait::Bound, 2, &[(0.0, 0.0), (0.5, 0.5)]);
+        assert_orders(Gait::Bound, 1, &[(0.0, 0.0)]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn custom_reads_left_then_right_and_defaults_to_zero() {
+        let gait = Gait::Custom(vec![0.1, 0.2, 0.3]);
+
+        assert_orders(gait.clone(), 2, &[(0.1, 0.2), (0.3, 0.0)]);
+        assert_orders(gait, 1, &[(0.1, 0.2)]);
+    }
+}